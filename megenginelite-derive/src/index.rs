@@ -3,114 +3,153 @@ use quote::quote;
 use syn::{
     parse::{Parse, ParseStream, Result},
     punctuated::Punctuated,
-    Expr, ExprRange, Token,
+    spanned::Spanned,
+    Error, Expr, ExprLit, ExprRange, ExprUnary, Lit, Token, UnOp,
 };
 
-struct Index {
-    start: Option<Box<Expr>>,
-    end: Option<Box<Expr>>,
-    step: Option<Expr>,
-    one: bool,
+enum Index {
+    One(Expr),
+    Range {
+        start: Option<Box<Expr>>,
+        end: Option<Box<Expr>>,
+        step: Option<Expr>,
+        inclusive: bool,
+    },
+    Ellipsis(Token![...]),
+    NewAxis,
 }
 
 pub struct IndexSequence {
     seq: Punctuated<Index, Token![,]>,
 }
 
+fn is_new_axis(expr: &Expr) -> bool {
+    matches!(expr, Expr::Path(p) if p.qself.is_none() && p.path.is_ident("NewAxis"))
+}
+
+fn check_step(step: &Expr) -> Result<()> {
+    let invalid = match step {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(n), ..
+        }) => n.base10_parse::<u64>()? == 0,
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_), ..
+        }) => true,
+        _ => false,
+    };
+    if invalid {
+        Err(Error::new(step.span(), "step must be greater than 0"))
+    } else {
+        Ok(())
+    }
+}
+
 impl Parse for Index {
     fn parse(input: ParseStream) -> Result<Self> {
-        let mut start: Option<_> = None;
-        let mut end: Option<_> = None;
-        let mut step: Option<Expr> = None;
-
-        let mut one = false;
-        if input.fork().parse::<ExprRange>().is_ok() {
-            if let Ok(ExprRange {
+        let index = if input.peek(Token![...]) {
+            Index::Ellipsis(input.parse()?)
+        } else if input.fork().parse::<ExprRange>().is_ok() {
+            let ExprRange {
                 from, limits, to, ..
-            }) = input.parse::<ExprRange>()
-            {
-                if matches!(limits, syn::RangeLimits::Closed(_)) {
-                    one = true;
-                }
-                start = from;
-                end = to;
+            } = input.parse::<ExprRange>()?;
+            Index::Range {
+                start: from,
+                end: to,
+                step: None,
+                inclusive: matches!(limits, syn::RangeLimits::Closed(_)),
             }
         } else {
-            start = Some(Box::new(input.parse::<Expr>()?));
-            one = true;
-        }
+            let expr = input.parse::<Expr>()?;
+            if is_new_axis(&expr) {
+                Index::NewAxis
+            } else {
+                Index::One(expr)
+            }
+        };
 
         if input.peek(Token![;]) {
-            input.parse::<Token![;]>()?;
-            step = Some(input.parse()?);
+            let semi = input.parse::<Token![;]>()?;
+            let step: Expr = input.parse()?;
+            check_step(&step)?;
+            match index {
+                Index::Range {
+                    start,
+                    end,
+                    inclusive,
+                    ..
+                } => Ok(Index::Range {
+                    start,
+                    end,
+                    step: Some(step),
+                    inclusive,
+                }),
+                _ => Err(Error::new(
+                    semi.span(),
+                    "step is only allowed after a range",
+                )),
+            }
+        } else {
+            Ok(index)
         }
-
-        Ok(Index {
-            start,
-            end,
-            step,
-            one,
-        })
     }
 }
 
 impl Parse for IndexSequence {
     fn parse(input: ParseStream) -> Result<Self> {
-        Ok(IndexSequence {
-            seq: input.parse_terminated(Index::parse).unwrap_or_default(),
-        })
+        let seq = input.parse_terminated(Index::parse)?;
+        let second = seq
+            .iter()
+            .filter_map(|x| match x {
+                Index::Ellipsis(token) => Some(token.span()),
+                _ => None,
+            })
+            .nth(1);
+        if let Some(span) = second {
+            return Err(Error::new(
+                span,
+                "an index can only have a single ellipsis `...`",
+            ));
+        }
+        Ok(IndexSequence { seq })
     }
 }
 
 pub fn expand(index_seq: IndexSequence) -> TokenStream {
-    let start: Vec<_> = index_seq
-        .seq
-        .iter()
-        .map(|index| {
-            if let Some(start) = index.start.as_ref() {
-                quote! (#start)
-            } else {
-                quote!(0)
-            }
-        })
-        .collect();
-    let end: Vec<_> = index_seq
-        .seq
-        .iter()
-        .map(|index| {
-            let end = &index.end;
-            if let Some(end) = end {
-                if index.one {
-                    quote! ( Some((#end) + 1) )
-                } else {
-                    quote! ( Some(#end) )
-                }
-            } else {
-                if index.one {
-                    let start = index.start.as_ref().unwrap();
-                    quote!(Some((#start) + 1))
-                } else {
-                    quote!(None)
+    let elems = index_seq.seq.iter().map(|index| match index {
+        Index::One(i) => quote!(megenginelite_rs::SliceElem::Index((#i) as isize)),
+        Index::Range {
+            start,
+            end,
+            step,
+            inclusive,
+        } => {
+            let start = match start {
+                Some(start) => quote!(Some((#start) as isize)),
+                None => quote!(None),
+            };
+            let end = match end {
+                Some(end) => quote!(Some((#end) as isize)),
+                None => quote!(None),
+            };
+            let step = match step {
+                Some(step) => quote!((#step) as isize),
+                None => quote!(1),
+            };
+            quote! {
+                megenginelite_rs::SliceElem::Range {
+                    start: #start,
+                    end: #end,
+                    step: #step,
+                    inclusive: #inclusive,
                 }
             }
-        })
-        .collect();
-    let step: Vec<_> = index_seq
-        .seq
-        .iter()
-        .map(|index| {
-            if let Some(step) = &index.step {
-                quote!(#step)
-            } else {
-                quote!(1)
-            }
-        })
-        .collect();
+        }
+        Index::Ellipsis(_) => quote!(megenginelite_rs::SliceElem::Ellipsis),
+        Index::NewAxis => quote!(megenginelite_rs::SliceElem::NewAxis),
+    });
     quote! {
         megenginelite_rs::SliceInfo {
-            start: &[#(#start),*],
-            end: &[#(#end),*],
-            step: &[#(#step),*],
+            elems: &[#(#elems),*],
         }
     }
 }
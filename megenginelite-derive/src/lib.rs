@@ -1,4 +1,5 @@
 mod index;
+mod shape;
 
 use proc_macro::TokenStream;
 use syn::parse_macro_input;
@@ -9,6 +10,11 @@ use syn::parse_macro_input;
 /// - index: an index to use for taking a subview with respect to that axis.
 /// - range: a range with step size 1 to use for slicing that axis.
 /// - range;step: a range with step size step to use for slicing that axis. (step >= 1)
+/// - `...`: full ranges for as many axes as needed, at most one is allowed.
+/// - `NewAxis`: insert a new axis with size 1.
+///
+/// Negative indices and negative range bounds are counted from the end of that
+/// axis, they are resolved against the shape of tensor in `Tensor::slice`.
 ///
 /// # Example
 /// ```no_run
@@ -16,9 +22,52 @@ use syn::parse_macro_input;
 /// idx!(0..2, 1, ..3;5);
 /// idx!(0..2, 2..3;5);
 /// idx!(0.., ..3;5, ..3, .., ..;4, 0..;4);
+/// idx!(-1, ..-2, -3..;2);
+/// idx!(..., 0, NewAxis);
 /// ```
 #[proc_macro]
 pub fn idx(input: TokenStream) -> TokenStream {
     let sequence = parse_macro_input!(input as index::IndexSequence);
     index::expand(sequence).into()
 }
+
+/// A helper macro used to describe the shape of tensor.
+///
+/// The syntax is `shape![dim[,dim[,...]]]`, where dim is any expression which can be
+/// cast to `usize`. The number of dims must not exceed `LAYOUT_MAX_DIM`.
+///
+/// It expands to `[usize; N]`, which can be used as the `shapes` of `Layout`. If one of
+/// the dims is the placeholder `-1`, it expands to `[i32; N]` instead, which can be
+/// used by `Tensor::reshape`.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_derive::shape;
+/// const N: usize = 4;
+/// let a: [usize; 4] = shape!(1, 3, 224, 224);
+/// let b: [usize; 3] = shape!(N, N * 2, 3);
+/// let c: [i32; 2] = shape!(N, -1);
+/// ```
+#[proc_macro]
+pub fn shape(input: TokenStream) -> TokenStream {
+    let sequence = parse_macro_input!(input as shape::ShapeSequence);
+    shape::expand(sequence).into()
+}
+
+/// A helper macro used to build a `Layout` with the data type and shape.
+///
+/// The syntax is `layout!(dtype; dim[,dim[,...]])`, where dtype is one of
+/// `f32`, `f16`, `i32`, `i16`, `i8`, `u32`, `u16`, `u8`, `i64`, or an expression of
+/// `LiteDataType`, and dims are same as [`shape!`] without the placeholder `-1`.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// let layout = layout!(f32; 1, 3, 224, 224);
+/// let layout = layout!(DataType::U8; 10, 10);
+/// ```
+#[proc_macro]
+pub fn layout(input: TokenStream) -> TokenStream {
+    let layout = parse_macro_input!(input as shape::LayoutInput);
+    shape::expand_layout(layout).into()
+}
@@ -4,43 +4,150 @@ use syn::{
     parse::{Parse, ParseStream, Result},
     punctuated::Punctuated,
     spanned::Spanned,
-    Error, LitInt, Token,
+    Error, Expr, ExprLit, ExprUnary, Ident, Lit, Token, UnOp,
 };
 
 static LAYOUT_MAX_DIM: usize = 7;
 
 pub struct ShapeSequence {
-    seq: Punctuated<LitInt, Token![,]>,
+    seq: Punctuated<Expr, Token![,]>,
+}
+
+pub struct LayoutInput {
+    data_type: Expr,
+    shape: ShapeSequence,
 }
 
 impl Parse for ShapeSequence {
     fn parse(input: ParseStream) -> Result<Self> {
         Ok(ShapeSequence {
-            seq: input.parse_terminated(LitInt::parse).unwrap_or_default(),
+            seq: input.parse_terminated(Expr::parse)?,
         })
     }
 }
 
-pub fn expand(shape: ShapeSequence) -> TokenStream {
+impl Parse for LayoutInput {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let data_type = input.parse()?;
+        input.parse::<Token![;]>()?;
+        Ok(LayoutInput {
+            data_type,
+            shape: input.parse()?,
+        })
+    }
+}
+
+/// Return the value of a negative integer literal
+fn negative_literal(expr: &Expr) -> Option<Result<i64>> {
+    match expr {
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => match expr.as_ref() {
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(n), ..
+            }) => Some(n.base10_parse::<i64>().map(|x| -x)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn data_type(expr: &Expr) -> Result<TokenStream> {
+    let ident = match expr {
+        Expr::Path(p) if p.qself.is_none() => p.path.get_ident(),
+        _ => None,
+    };
+    let name = match ident.map(Ident::to_string).as_deref() {
+        Some("f32") => "F32",
+        Some("f16") => "F16",
+        Some("i32") => "I32",
+        Some("i16") => "I16",
+        Some("i8") => "I8",
+        Some("u32") => "U32",
+        Some("u16") => "U16",
+        Some("u8") => "U8",
+        Some("i64") => "I64",
+        Some(x @ ("f64" | "u64" | "i128" | "u128" | "isize" | "usize" | "bool")) => {
+            return Err(Error::new(
+                expr.span(),
+                format!("The data type `{}` is not supported", x),
+            ))
+        }
+        _ => return Ok(quote!(#expr)),
+    };
+    let name = Ident::new(name, expr.span());
+    Ok(quote!(megenginelite_rs::DataType::#name))
+}
+
+fn check(shape: &ShapeSequence, allow_placeholder: bool) -> Result<bool> {
     if shape.seq.len() > LAYOUT_MAX_DIM {
-        return Error::new(
+        return Err(Error::new(
             shape.seq.span(),
             format!(
                 "The maximum dim supported does not exceed {}",
                 LAYOUT_MAX_DIM
             ),
-        )
-        .to_compile_error();
+        ));
+    }
+    let mut placeholder = false;
+    for expr in shape.seq.iter() {
+        match negative_literal(expr).transpose()? {
+            Some(-1) if !allow_placeholder => {
+                return Err(Error::new(
+                    expr.span(),
+                    "The placeholder `-1` is only allowed in a reshape shape",
+                ))
+            }
+            Some(-1) if placeholder => {
+                return Err(Error::new(
+                    expr.span(),
+                    "Only one dim can be the placeholder `-1`",
+                ))
+            }
+            Some(-1) => placeholder = true,
+            Some(_) => return Err(Error::new(expr.span(), "The dim must not be negative")),
+            None => {}
+        }
     }
-    let rest = vec![quote!(0); LAYOUT_MAX_DIM - shape.seq.len()];
+    Ok(placeholder)
+}
+
+pub fn expand(shape: ShapeSequence) -> TokenStream {
+    let placeholder = match check(&shape, true) {
+        Ok(placeholder) => placeholder,
+        Err(e) => return e.to_compile_error(),
+    };
     let shape = &shape.seq;
     if shape.is_empty() {
+        quote!([0usize; 0])
+    } else if placeholder {
+        let shape = shape.iter();
         quote! {
-            [#(#rest),*]
+            [#((#shape) as i32),*]
         }
     } else {
+        let shape = shape.iter();
         quote! {
-            [#shape, #(#rest),*]
+            [#((#shape) as usize),*]
+        }
+    }
+}
+
+pub fn expand_layout(layout: LayoutInput) -> TokenStream {
+    let data_type = match data_type(&layout.data_type) {
+        Ok(data_type) => data_type,
+        Err(e) => return e.to_compile_error(),
+    };
+    if let Err(e) = check(&layout.shape, false) {
+        return e.to_compile_error();
+    }
+    let shape = layout.shape.seq.iter();
+    quote! {
+        megenginelite_rs::Layout {
+            data_type: #data_type,
+            shapes: &[#((#shape) as usize),*],
         }
     }
 }
@@ -0,0 +1,281 @@
+use super::ffi::LiteDeviceType;
+use super::tensor::*;
+use super::{DataType, LiteError, LiteResult};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// The smallest size class in bytes
+const MIN_CLASS: usize = 256;
+
+/// Round `nbytes` up to the size class, there are 4 classes in each power of two
+fn size_class(nbytes: usize) -> usize {
+    if nbytes <= MIN_CLASS {
+        return MIN_CLASS;
+    }
+    let log2 = usize::BITS - 1 - (nbytes - 1).leading_zeros();
+    let step = 1 << (log2 - 2);
+    // `step` is a power of two
+    (nbytes + step - 1) & !(step - 1)
+}
+
+#[derive(Clone, Copy)]
+enum Memory {
+    Host,
+    Pinned(LiteDeviceType, i32),
+    Device(LiteDeviceType, i32),
+}
+
+impl Memory {
+    fn tensor(self) -> LiteResult<Tensor> {
+        match self {
+            Memory::Host => Tensor::host(),
+            Memory::Pinned(ty, dev_id) => Tensor::pinned_host(ty, dev_id),
+            Memory::Device(ty, dev_id) => Tensor::device(ty, dev_id),
+        }
+    }
+}
+
+struct State {
+    /// The free blocks of each size class
+    free: BTreeMap<usize, Vec<Tensor>>,
+    /// The bytes of all blocks, including the free blocks
+    allocated: usize,
+    /// The bytes of the blocks in use
+    in_use: usize,
+    cap: usize,
+}
+
+impl State {
+    /// Release free blocks until `nbytes` more bytes can be allocated under the cap
+    fn reserve(&mut self, nbytes: usize) -> bool {
+        while self.allocated + nbytes > self.cap {
+            // release the largest block first
+            let class = match self.free.keys().next_back() {
+                Some(&class) => class,
+                None => return false,
+            };
+            let blocks = self.free.get_mut(&class).unwrap();
+            blocks.pop();
+            if blocks.is_empty() {
+                self.free.remove(&class);
+            }
+            self.allocated -= class;
+        }
+        true
+    }
+}
+
+struct Inner {
+    memory: Memory,
+    state: Mutex<State>,
+}
+
+/// A block of the arena, which is given back when the tensors using it are dropped
+struct Block {
+    mem: Option<Tensor>,
+    class: usize,
+    arena: Arc<Inner>,
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        let mut state = self.arena.state.lock().unwrap();
+        state.in_use -= self.class;
+        match self.mem.take() {
+            Some(mem) if state.allocated <= state.cap => {
+                state.free.entry(self.class).or_default().push(mem)
+            }
+            _ => state.allocated -= self.class,
+        }
+    }
+}
+
+/// A tensor arena which serves tensors of arbitrary layouts
+///
+/// The memory is allocated in blocks of size classes, a block is reused by the later
+/// requests of the same size class after the tensor and its views are dropped. The free
+/// blocks are released when the memory cap is exceeded, or by [`TensorArena::shrink`].
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # fn main() -> LiteResult<()> {
+/// let arena = TensorArena::host(256 << 20)?;
+/// {
+///     let a = arena.alloc(layout!(f32; 1, 3, 224, 224))?;
+///     let b = arena.alloc(layout!(u8; 1080, 1920, 3))?;
+/// }
+/// // the memory of `a` is reused
+/// let c = arena.alloc(layout!(f32; 1, 3, 220, 220))?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct TensorArena {
+    inner: Arc<Inner>,
+}
+
+impl TensorArena {
+    fn new(memory: Memory, cap: usize) -> LiteResult<Self> {
+        Ok(TensorArena {
+            inner: Arc::new(Inner {
+                memory,
+                state: Mutex::new(State {
+                    free: BTreeMap::new(),
+                    allocated: 0,
+                    in_use: 0,
+                    cap,
+                }),
+            }),
+        })
+    }
+
+    /// Create an arena with host memory of at most `cap` bytes, see also [`Tensor::host()`]
+    pub fn host(cap: usize) -> LiteResult<Self> {
+        Self::new(Memory::Host, cap)
+    }
+
+    /// Create an arena with device memory of at most `cap` bytes, see also
+    /// [`Tensor::device()`]
+    pub fn device(ty: LiteDeviceType, dev_id: i32, cap: usize) -> LiteResult<Self> {
+        Self::new(Memory::Device(ty, dev_id), cap)
+    }
+
+    /// Create an arena with pinned host memory of at most `cap` bytes, see also
+    /// [`Tensor::pinned_host()`]
+    pub fn pinned_host(ty: LiteDeviceType, dev_id: i32, cap: usize) -> LiteResult<Self> {
+        Self::new(Memory::Pinned(ty, dev_id), cap)
+    }
+
+    /// Allocate a tensor with `layout`, the memory is given back to the arena when the
+    /// tensor and its views are dropped.
+    ///
+    /// Return an error if the memory cap would be exceeded even after releasing all
+    /// free blocks.
+    pub fn alloc(&self, layout: Layout) -> LiteResult<Tensor> {
+        let nbytes = layout.shapes.iter().product::<usize>() * DataType::width(layout.data_type);
+        let class = size_class(nbytes);
+
+        let cached = {
+            let mut state = self.inner.state.lock().unwrap();
+            let cached = state.free.get_mut(&class).and_then(|v| v.pop());
+            if cached.is_none() && !state.reserve(class) {
+                return Err(LiteError::OutOfMemory(format!(
+                    "allocating {} bytes exceeds the arena cap {} bytes, {} bytes in use",
+                    class, state.cap, state.in_use
+                )));
+            }
+            if cached.is_none() {
+                state.allocated += class;
+            }
+            state.in_use += class;
+            cached
+        };
+        let mut block = Block {
+            mem: cached,
+            class,
+            arena: self.inner.clone(),
+        };
+        // the block is given back on error
+        if block.mem.is_none() {
+            let mut mem = self.inner.memory.tensor()?;
+            mem.set_layout(Layout {
+                data_type: DataType::U8,
+                shapes: &[class],
+            });
+            block.mem = Some(mem);
+        }
+
+        let mut tensor = self.inner.memory.tensor()?;
+        tensor.set_layout(layout);
+        let p = block.mem.as_mut().unwrap().as_ptr_mut::<u8>();
+        unsafe { tensor.reset_memory(p as *mut _, nbytes, Arc::new(block))? };
+        Ok(tensor)
+    }
+
+    /// Release all free blocks
+    pub fn shrink(&self) {
+        let mut state = self.inner.state.lock().unwrap();
+        let free = std::mem::take(&mut state.free);
+        for (class, blocks) in free {
+            state.allocated -= class * blocks.len();
+        }
+    }
+
+    /// Change the memory cap, the free blocks are released if the cap is exceeded, and
+    /// the blocks in use are released when they are given back.
+    pub fn set_cap(&self, cap: usize) {
+        let mut state = self.inner.state.lock().unwrap();
+        state.cap = cap;
+        state.reserve(0);
+    }
+
+    /// The memory cap in bytes
+    pub fn cap(&self) -> usize {
+        self.inner.state.lock().unwrap().cap
+    }
+
+    /// The bytes of all blocks, including the free blocks
+    pub fn allocated_bytes(&self) -> usize {
+        self.inner.state.lock().unwrap().allocated
+    }
+
+    /// The bytes of the blocks in use
+    pub fn in_use_bytes(&self) -> usize {
+        self.inner.state.lock().unwrap().in_use
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn test_size_class() {
+        assert_eq!(size_class(0), 256);
+        assert_eq!(size_class(256), 256);
+        assert_eq!(size_class(257), 320);
+        assert_eq!(size_class(1000), 1024);
+        assert_eq!(size_class(1025), 1280);
+    }
+
+    #[test]
+    fn test_arena() -> LiteResult<()> {
+        let arena = TensorArena::host(4096)?;
+        let a = arena.alloc(layout!(f32; 16, 16))?;
+        assert_eq!(a.shape(), &[16, 16]);
+        assert_eq!(arena.in_use_bytes(), 1024);
+        let p = a.as_ptr::<u8>();
+        let view = a.slice(idx![1])?;
+        drop(a);
+        assert_eq!(arena.in_use_bytes(), 1024);
+        drop(view);
+        assert_eq!(arena.in_use_bytes(), 0);
+        assert_eq!(arena.allocated_bytes(), 1024);
+
+        let b = arena.alloc(layout!(u8; 900))?;
+        assert_eq!(b.as_ptr::<u8>(), p);
+        assert_eq!(arena.allocated_bytes(), 1024);
+
+        let c = arena.alloc(layout!(u8; 3000))?;
+        assert_eq!(arena.allocated_bytes(), 4096);
+        assert!(matches!(
+            arena.alloc(layout!(u8; 3000)),
+            Err(LiteError::OutOfMemory(_))
+        ));
+        drop(c);
+        assert_eq!(arena.allocated_bytes(), 4096);
+
+        // the free block of `c` is released to serve the request
+        let d = arena.alloc(layout!(u8; 2000))?;
+        assert_eq!(arena.allocated_bytes(), 3072);
+        arena.set_cap(2048);
+        drop(d);
+        assert_eq!(arena.allocated_bytes(), 1024);
+        drop(b);
+        arena.shrink();
+        assert_eq!(arena.allocated_bytes(), 0);
+        Ok(())
+    }
+}
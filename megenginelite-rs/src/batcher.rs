@@ -0,0 +1,305 @@
+//! The dynamic batching of single-sample requests
+
+use super::{idx, utils, DataType, Layout, LiteError, LiteResult, Network, Tensor};
+use crate::ffi::LiteDataType;
+use async_channel::{bounded, unbounded, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+struct Request {
+    sample: Tensor,
+    response: Sender<LiteResult<Vec<Tensor>>>,
+}
+
+/// A dynamic batching scheduler, which groups the single-sample requests up to
+/// `max_batch` samples or `max_latency`, and executes the network once for each group
+///
+/// The network is owned by a worker thread, the first dim of the input is the batch
+/// dim. Each request is resolved with its own outputs, which are in the order of
+/// [`Network::output_names`] and without the batch dim.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # use std::time::Duration;
+/// # #[tokio::main]
+/// # async fn main() -> LiteResult<()> {
+/// let network = Network::builder().build("model_path")?;
+/// let batcher = Batcher::new(network, "data", 16, Duration::from_millis(5))?;
+/// let mut sample = Tensor::host()?;
+/// sample.set_layout(layout!(f32; 3, 224, 224));
+/// // fill the sample ...
+/// let outputs = batcher.infer(sample).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Batcher {
+    requests: Sender<Request>,
+    shape: Vec<usize>,
+    data_type: LiteDataType,
+    stats: Arc<Mutex<BatcherStats>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+/// The grouping of the [`Batcher`], see also [`Batcher::stats`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatcherStats {
+    /// The number of executed batches, including the failed batches
+    pub batches: u64,
+    /// The number of samples in the executed batches
+    pub samples: u64,
+    /// The number of batches of `i + 1` samples at `i`, up to `max_batch` samples
+    pub sizes: Vec<u64>,
+}
+
+impl Batcher {
+    /// Create a batcher which feeds the input `input` of `network`, at most `max_batch`
+    /// samples are grouped, and a group waits at most `max_latency` for more samples.
+    pub fn new(
+        network: Network,
+        input: &str,
+        max_batch: usize,
+        max_latency: Duration,
+    ) -> LiteResult<Self> {
+        let tensor = network.io_tensor(input).ok_or_else(|| {
+            LiteError::MGELiteError(format!("the network has no input named {}", input))
+        })?;
+        if tensor.shape().is_empty() || max_batch == 0 {
+            return Err(LiteError::InvalidLayout(format!(
+                "cannot batch the input {} of shape {:?} up to {} samples",
+                input,
+                tensor.shape(),
+                max_batch
+            )));
+        }
+        let (requests, r) = unbounded();
+        let stats = Arc::new(Mutex::new(BatcherStats {
+            batches: 0,
+            samples: 0,
+            sizes: vec![0; max_batch],
+        }));
+        let worker = Worker {
+            network,
+            input: input.to_owned(),
+            shape: tensor.shape()[1..].to_vec(),
+            data_type: tensor.dtype(),
+            max_batch,
+            max_latency,
+            stats: stats.clone(),
+        };
+        Ok(Batcher {
+            requests,
+            shape: worker.shape.clone(),
+            data_type: worker.data_type,
+            stats,
+            worker: Some(std::thread::spawn(move || worker.run(r))),
+        })
+    }
+
+    /// Request the outputs of `sample`, whose shape is the input shape without the batch
+    /// dim
+    pub async fn infer(&self, sample: Tensor) -> LiteResult<Vec<Tensor>> {
+        if sample.shape() != self.shape.as_slice() || sample.dtype() != self.data_type {
+            return Err(LiteError::InvalidLayout(format!(
+                "the sample of {} {:?} does not match the input of {} {:?}",
+                DataType::name(sample.dtype()),
+                sample.shape(),
+                DataType::name(self.data_type),
+                self.shape
+            )));
+        }
+        let (response, r) = bounded(1);
+        let exited = || LiteError::MGELiteError("the batcher worker exited".into());
+        self.requests
+            .send(Request { sample, response })
+            .await
+            .map_err(|_| exited())?;
+        r.recv().await.map_err(|_| exited())?
+    }
+
+    /// Request the outputs of `sample`, will block the current thread, see also
+    /// [`Batcher::infer`]
+    pub fn infer_blocking(&self, sample: Tensor) -> LiteResult<Vec<Tensor>> {
+        utils::block_on(self.infer(sample), None).unwrap()
+    }
+
+    /// Get a snapshot of the grouping of the executed batches
+    pub fn stats(&self) -> BatcherStats {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Batcher {
+    fn drop(&mut self) {
+        // the pending requests are still served
+        self.requests.close();
+        if let Some(worker) = self.worker.take() {
+            worker.join().ok();
+        }
+    }
+}
+
+struct Worker {
+    network: Network,
+    input: String,
+    shape: Vec<usize>,
+    data_type: LiteDataType,
+    max_batch: usize,
+    max_latency: Duration,
+    stats: Arc<Mutex<BatcherStats>>,
+}
+
+impl Worker {
+    fn run(mut self, requests: Receiver<Request>) {
+        while let Some(Ok(first)) = utils::block_on(requests.recv(), None) {
+            let deadline = Instant::now() + self.max_latency;
+            let mut batch = vec![first];
+            while batch.len() < self.max_batch {
+                match utils::block_on(requests.recv(), Some(deadline)) {
+                    Some(Ok(request)) => batch.push(request),
+                    _ => break,
+                }
+            }
+            let rst = self.exec(&batch);
+            {
+                let mut stats = self.stats.lock().unwrap();
+                stats.batches += 1;
+                stats.samples += batch.len() as u64;
+                stats.sizes[batch.len() - 1] += 1;
+            }
+            match rst {
+                Ok(outputs) => {
+                    for (request, outputs) in batch.iter().zip(outputs) {
+                        request.response.try_send(Ok(outputs)).ok();
+                    }
+                }
+                Err(e) => {
+                    let e = Arc::new(e);
+                    for request in &batch {
+                        let e = LiteError::BatchFailed(e.clone());
+                        request.response.try_send(Err(e)).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Execute the network with the batch, and return the outputs of each request
+    fn exec(&mut self, batch: &[Request]) -> LiteResult<Vec<Vec<Tensor>>> {
+        let mut shape = vec![batch.len()];
+        shape.extend_from_slice(&self.shape);
+        let mut input = self.network.io_tensor(&self.input).unwrap();
+        input.set_layout(Layout {
+            shapes: &shape,
+            data_type: self.data_type,
+        });
+        // the indexed batch dim is kept by `slice`
+        let sample_shape: Vec<_> = self.shape.iter().map(|&x| x as i32).collect();
+        for (i, request) in batch.iter().enumerate() {
+            let mut dst = input.slice(idx![i])?;
+            dst.reshape(&sample_shape)?;
+            dst.copy_from(&request.sample)?;
+        }
+
+        self.network.exec_wait()?;
+
+        let outputs = self
+            .network
+            .output_names()
+            .into_iter()
+            .map(|name| self.network.io_tensor(name).unwrap())
+            .collect::<Vec<_>>();
+        (0..batch.len())
+            .map(|i| {
+                // the outputs are copied because they are overwritten by the next batch
+                outputs
+                    .iter()
+                    .map(|output| {
+                        let mut output = output.slice(idx![i])?.try_clone()?;
+                        let shape: Vec<_> = output.shape()[1..].iter().map(|&x| x as i32).collect();
+                        if !shape.is_empty() {
+                            output.reshape(&shape)?;
+                        }
+                        Ok(output)
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn sample(value: f32) -> LiteResult<Tensor> {
+        let mut sample = Tensor::host()?;
+        sample.set_layout(layout!(f32; 3, 224, 224));
+        sample.as_slice_mut::<f32>()?.fill(value);
+        Ok(sample)
+    }
+
+    #[test]
+    fn test_batcher() -> LiteResult<()> {
+        let network = Network::builder().build(crate::model_path())?;
+        // the latency is long enough for all requests to be sent
+        let batcher = Arc::new(Batcher::new(network, "data", 4, Duration::from_secs(1))?);
+        let handles: Vec<_> = (0..6)
+            .map(|_| {
+                let batcher = batcher.clone();
+                std::thread::spawn(move || batcher.infer_blocking(sample(0.0)?))
+            })
+            .collect();
+        for handle in handles {
+            let outputs = handle.join().unwrap()?;
+            assert_eq!(outputs.len(), 1);
+            assert_eq!(outputs[0].shape(), &[1000]);
+        }
+        let stats = batcher.stats();
+        assert_eq!(stats.batches, 2);
+        assert_eq!(stats.samples, 6);
+        assert_eq!(stats.sizes, [0, 1, 0, 1]);
+
+        let mut sample = Tensor::host()?;
+        sample.set_layout(layout!(f32; 1, 3, 224, 224));
+        assert!(matches!(
+            batcher.infer_blocking(sample),
+            Err(LiteError::InvalidLayout(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batcher_values() -> LiteResult<()> {
+        let mut network = Network::builder().build(crate::model_path())?;
+        let name = network.output_names()[0].to_owned();
+        let mut expected = vec![];
+        for i in 0..4 {
+            let mut input = network.io_tensor("data").unwrap();
+            input.set_layout(layout!(f32; 1, 3, 224, 224));
+            input.as_slice_mut::<f32>()?.fill(i as f32);
+            network.exec_wait()?;
+            let mut output = network.io_tensor(&name).unwrap().try_clone()?;
+            output.reshape(&[1000])?;
+            expected.push(output);
+        }
+
+        // the requests are sent at the same time, so they are executed as one batch
+        let batcher = Batcher::new(network, "data", 4, Duration::from_secs(1))?;
+        let (a, b, c, d) = tokio::join!(
+            batcher.infer(sample(0.0)?),
+            batcher.infer(sample(1.0)?),
+            batcher.infer(sample(2.0)?),
+            batcher.infer(sample(3.0)?),
+        );
+        for (outputs, expected) in [a?, b?, c?, d?].iter().zip(&expected) {
+            assert_eq!(outputs[0].shape(), &[1000]);
+            assert!(outputs[0].allclose(expected, 1e-5, 1e-5)?);
+        }
+        assert_eq!(batcher.stats().sizes, [0, 0, 0, 1]);
+        Ok(())
+    }
+}
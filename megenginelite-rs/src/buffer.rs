@@ -0,0 +1,272 @@
+//! The user memory owned or borrowed by tensors
+
+use super::*;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+/// The marker owner of the memory borrowed by a [`TensorView`]
+pub(crate) struct Borrowed;
+
+/// A zeroed host memory with a given alignment, see also [`Tensor::with_aligned_buffer`]
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    layout: std::alloc::Layout,
+}
+
+unsafe impl Send for AlignedBuffer {}
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    /// Allocate `nbytes` bytes aligned to `align`, which must be a power of two.
+    pub fn new(nbytes: usize, align: usize) -> LiteResult<AlignedBuffer> {
+        let layout = std::alloc::Layout::from_size_align(nbytes, align)
+            .map_err(|e| LiteError::InvalidLayout(e.to_string()))?;
+        let ptr = if nbytes == 0 {
+            // a dangling pointer with the alignment
+            NonNull::new(align as *mut u8)
+        } else {
+            NonNull::new(unsafe { std::alloc::alloc_zeroed(layout) })
+        };
+        let ptr = ptr.unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+        Ok(AlignedBuffer { ptr, layout })
+    }
+
+    /// The number of bytes
+    pub fn len(&self) -> usize {
+        self.layout.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The alignment in bytes
+    pub fn alignment(&self) -> usize {
+        self.layout.align()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len()) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len()) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        if self.layout.size() > 0 {
+            unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+        }
+    }
+}
+
+/// A host tensor whose storage is the memory borrowed from `&'a mut [T]`, which derefs
+/// to the tensor, the memory is written by [`TensorView::as_slice_mut`] and
+/// [`TensorView::fill_zero`]
+///
+/// The memory cannot be shared to other tensors, so [`Tensor::slice`] fails on it,
+/// use [`Tensor::copy_from`] to feed it into a network.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # fn main() -> LiteResult<()> {
+/// let network = Network::builder().build("model_path")?;
+/// let mut input = network.io_tensor("data").unwrap();
+/// let mut data = vec![0f32; 3 * 224 * 224];
+/// let view = TensorView::new(layout!(f32; 1, 3, 224, 224), &mut data)?;
+/// input.copy_from(&view)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TensorView<'a> {
+    tensor: Tensor,
+    phantom: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> TensorView<'a> {
+    /// Borrow `data` as the storage of a host tensor with `layout`
+    pub fn new<T: Copy>(layout: Layout, data: &'a mut [T]) -> LiteResult<TensorView<'a>> {
+        let nbytes = std::mem::size_of_val(data);
+        let tensor = Tensor::with_owner(layout, data.as_mut_ptr() as *mut u8, nbytes, |t| {
+            t.check_element::<T>()?;
+            Ok(Arc::new(Borrowed))
+        })?;
+        Ok(TensorView {
+            tensor,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Get the mutable slice of the memory, see also [`Tensor::as_slice_mut`]
+    pub fn as_slice_mut<T>(&mut self) -> LiteResult<&mut [T]> {
+        self.tensor.as_slice_mut()
+    }
+
+    /// Fill the memory with zero, see also [`Tensor::fill_zero`]
+    pub fn fill_zero(&mut self) {
+        self.tensor.fill_zero()
+    }
+}
+
+impl<'a> Deref for TensorView<'a> {
+    type Target = Tensor;
+
+    fn deref(&self) -> &Tensor {
+        &self.tensor
+    }
+}
+
+impl Tensor {
+    /// A host tensor with `layout` whose storage is the memory at `p` with `nbytes`
+    /// bytes, which is kept alive by the owner returned from `owner`.
+    fn with_owner(
+        layout: Layout,
+        p: *mut u8,
+        nbytes: usize,
+        owner: impl FnOnce(&Tensor) -> LiteResult<Arc<dyn std::any::Any + Send + Sync>>,
+    ) -> LiteResult<Tensor> {
+        let mut tensor = Tensor::host()?;
+        tensor.set_layout(layout);
+        let owner = owner(&tensor)?;
+        if nbytes != tensor.nbytes() {
+            return Err(LiteError::InvalidLayout(format!(
+                "the buffer has {} bytes, but the layout requires {} bytes",
+                nbytes,
+                tensor.nbytes()
+            )));
+        }
+        if nbytes > 0 {
+            unsafe { tensor.reset_memory(p as *mut _, nbytes, owner)? };
+        }
+        Ok(tensor)
+    }
+
+    /// A host tensor with `layout` whose storage is `buffer`, which is freed when the
+    /// tensor and its views are dropped.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let data = vec![0f32; 3 * 224 * 224].into_boxed_slice();
+    /// let tensor = Tensor::with_buffer(layout!(f32; 1, 3, 224, 224), data)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_buffer<T: Copy + Send + Sync + 'static>(
+        layout: Layout,
+        mut buffer: Box<[T]>,
+    ) -> LiteResult<Tensor> {
+        let p = buffer.as_mut_ptr() as *mut u8;
+        let nbytes = std::mem::size_of_val(&*buffer);
+        Tensor::with_owner(layout, p, nbytes, |t| {
+            t.check_element::<T>()?;
+            Ok(Arc::new(buffer))
+        })
+    }
+
+    /// A host tensor with `layout` whose storage is `buffer`, see also
+    /// [`Tensor::with_buffer`]
+    pub fn with_aligned_buffer(layout: Layout, buffer: AlignedBuffer) -> LiteResult<Tensor> {
+        let p = buffer.ptr.as_ptr();
+        let nbytes = buffer.len();
+        Tensor::with_owner(layout, p, nbytes, |_| Ok(Arc::new(buffer)))
+    }
+
+    /// A host tensor with `layout` whose memory is aligned to `align` bytes, which must
+    /// be a power of two.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let tensor = Tensor::host_aligned(layout!(u8; 1080, 1920, 3), 4096)?;
+    /// assert!(tensor.alignment() >= 4096);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn host_aligned(layout: Layout, align: usize) -> LiteResult<Tensor> {
+        let nbytes = layout.shapes.iter().product::<usize>() * DataType::width(layout.data_type);
+        Tensor::with_aligned_buffer(layout, AlignedBuffer::new(nbytes, align)?)
+    }
+
+    /// The alignment in bytes of the memory address, that is the largest power of two
+    /// which divides it, or 0 if the tensor has no memory.
+    pub fn alignment(&self) -> usize {
+        match self.as_ptr::<u8>() as usize {
+            0 => 0,
+            p => 1 << p.trailing_zeros(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_with_buffer() -> LiteResult<()> {
+        let data: Box<[f32]> = (0..6).map(|x| x as f32).collect();
+        let p = data.as_ptr();
+        let tensor = Tensor::with_buffer(layout!(f32; 2, 3), data)?;
+        assert_eq!(tensor.as_ptr::<f32>(), p);
+        let view = tensor.slice(idx![1])?;
+        drop(tensor);
+        assert_eq!(view.as_slice::<f32>()?, &[3.0, 4.0, 5.0]);
+
+        let data = vec![0u8; 5].into_boxed_slice();
+        assert!(matches!(
+            Tensor::with_buffer(layout!(f32; 2, 3), data),
+            Err(LiteError::InvalidLayout(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_aligned_buffer() -> LiteResult<()> {
+        let mut buffer = AlignedBuffer::new(24, 64)?;
+        assert_eq!(buffer.as_slice().as_ptr() as usize % 64, 0);
+        buffer.as_mut_slice()[0] = 1;
+        let tensor = Tensor::with_aligned_buffer(layout!(u8; 4, 6), buffer)?;
+        assert_eq!(tensor.as_ptr::<u8>() as usize % 64, 0);
+        assert_eq!(tensor.as_slice::<u8>()?[..2], [1, 0]);
+        assert!(AlignedBuffer::new(24, 3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_aligned() -> LiteResult<()> {
+        let tensor = Tensor::host_aligned(layout!(f32; 3, 5), 4096)?;
+        assert!(tensor.alignment() >= 4096);
+        assert_eq!(tensor.nbytes(), 60);
+        let sub = tensor.slice(idx![1])?;
+        assert_eq!(sub.alignment(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tensor_view() -> LiteResult<()> {
+        let mut data = [1i32, 2, 3, 4];
+        let mut view = TensorView::new(layout!(i32; 2, 2), &mut data)?;
+        assert_eq!(view.shape(), &[2, 2]);
+        assert_eq!(view.as_slice::<i32>()?, &[1, 2, 3, 4]);
+        view.as_slice_mut::<i32>()?[3] = 5;
+        assert!(matches!(
+            view.slice(idx![0]),
+            Err(LiteError::BorrowedMemory)
+        ));
+        let copy = view.try_clone()?;
+        drop(view);
+        data[0] = 0;
+        assert_eq!(copy.as_slice::<i32>()?, &[1, 2, 3, 5]);
+        assert_eq!(data, [0, 2, 3, 5]);
+        TensorView::new(layout!(i32; 4), &mut data)?.fill_zero();
+        assert_eq!(data, [0; 4]);
+        Ok(())
+    }
+}
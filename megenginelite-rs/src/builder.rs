@@ -3,7 +3,7 @@
 use super::*;
 use crate::ffi::*;
 use std::ffi::CString;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Default network config
 pub fn default_config() -> LiteConfig {
@@ -29,33 +29,276 @@ impl<'a> Default for IO<'a> {
     }
 }
 
-impl<'a> IO<'a> {
+/// The owned configuration of network's input and output, see also [`IO`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IOConfig {
+    pub name: String,
+    pub is_host: bool,
+    pub io_type: LiteIOType,
+    pub data_type: LiteDataType,
+    pub shapes: Vec<usize>,
+}
+
+impl<'a> From<IO<'a>> for IOConfig {
+    fn from(io: IO<'a>) -> Self {
+        IOConfig {
+            name: io.name.to_owned(),
+            is_host: io.is_host,
+            io_type: io.io_type,
+            data_type: io.layout.data_type,
+            shapes: io.layout.shapes.to_vec(),
+        }
+    }
+}
+
+impl IOConfig {
     fn as_raw(&self) -> (CString, LiteIO) {
-        let name = CString::new(self.name).unwrap();
+        let name = CString::new(self.name.as_str()).unwrap();
         let name_ptr = name.as_ptr();
+        let layout = Layout {
+            shapes: &self.shapes,
+            data_type: self.data_type,
+        };
         (
             name,
             LiteIO {
                 name: name_ptr,
                 is_host: self.is_host as i32,
                 io_type: self.io_type,
-                config_layout: self.layout.as_raw(),
+                config_layout: layout.as_raw(),
             },
         )
     }
 }
 
+/// An option of the network which is set before loading the model, see also the methods
+/// of [`NetworkBuilder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NetworkOption {
+    /// See [`NetworkBuilder::cpu_inplace`]
+    CpuInplace,
+    /// See [`NetworkBuilder::tensorrt`]
+    TensorRT,
+    /// See [`NetworkBuilder::threads_number`]
+    ThreadsNumber(usize),
+    /// See [`NetworkBuilder::dev_id`]
+    DevId(i32),
+    /// See [`NetworkBuilder::stream_id`]
+    StreamId(i32),
+    /// See [`NetworkBuilder::algo_policy`]
+    AlgoPolicy(LiteAlgoSelectStrategy),
+    /// See [`NetworkBuilder::fastrun_config`]
+    FastrunConfig {
+        shared_batch_size: u32,
+        binary_equal_between_batch: i32,
+    },
+    /// See [`NetworkBuilder::workspace_limit`]
+    WorkspaceLimit(usize),
+    /// See [`NetworkBuilder::profile_performance`]
+    ProfilePerformance(PathBuf),
+    /// See [`NetworkBuilder::io_txt_dump`]
+    IoTxtDump(PathBuf),
+    /// See [`NetworkBuilder::io_bin_dump`]
+    IoBinDump(PathBuf),
+}
+
+impl NetworkOption {
+    fn apply(&self, net: LiteNetwork) {
+        unsafe {
+            match self {
+                NetworkOption::CpuInplace => {
+                    api().LITE_set_cpu_inplace_mode(net);
+                }
+                NetworkOption::TensorRT => {
+                    api().LITE_use_tensorrt(net);
+                }
+                NetworkOption::ThreadsNumber(nr_threads) => {
+                    api().LITE_set_cpu_threads_number(net, *nr_threads);
+                }
+                NetworkOption::DevId(dev_id) => {
+                    api().LITE_set_device_id(net, *dev_id);
+                }
+                NetworkOption::StreamId(stream_id) => {
+                    api().LITE_set_stream_id(net, *stream_id);
+                }
+                NetworkOption::AlgoPolicy(strategy) => {
+                    api().LITE_set_network_algo_policy(net, *strategy);
+                }
+                NetworkOption::FastrunConfig {
+                    shared_batch_size,
+                    binary_equal_between_batch,
+                } => {
+                    api().LITE_set_network_algo_fastrun_config(
+                        net,
+                        *shared_batch_size,
+                        *binary_equal_between_batch,
+                    );
+                }
+                NetworkOption::WorkspaceLimit(workspace_limit) => {
+                    api().LITE_set_network_algo_workspace_limit(net, *workspace_limit);
+                }
+                NetworkOption::ProfilePerformance(path) => {
+                    let path_str_c = utils::path_to_cstr(path);
+                    api().LITE_enable_profile_performance(net, path_str_c.as_ptr());
+                }
+                NetworkOption::IoTxtDump(path) => {
+                    let path_str_c = utils::path_to_cstr(path);
+                    api().LITE_enable_io_txt_dump(net, path_str_c.as_ptr());
+                }
+                NetworkOption::IoBinDump(path) => {
+                    let path_str_c = utils::path_to_cstr(path);
+                    api().LITE_enable_io_bin_dump(net, path_str_c.as_ptr());
+                }
+            }
+        }
+    }
+}
+
+macro_rules! options_data {
+    ($($field:ident),* $(,)?) => {
+        /// The plain data of [`LiteOptions`]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        struct OptionsData {
+            $($field: i32,)*
+        }
+
+        // the pattern and the struct expression below are exhaustive, so the list of
+        // fields fails to compile if it drifts from `LiteOptions`
+        impl From<&LiteOptions> for OptionsData {
+            fn from(options: &LiteOptions) -> Self {
+                let LiteOptions { $($field,)* } = *options;
+                OptionsData { $($field,)* }
+            }
+        }
+
+        impl From<OptionsData> for LiteOptions {
+            fn from(options: OptionsData) -> Self {
+                LiteOptions { $($field: options.$field,)* }
+            }
+        }
+    };
+}
+
+options_data!(
+    weight_preprocess,
+    fuse_preprocess,
+    fake_next_exec,
+    var_sanity_check_first_run,
+    const_shape,
+    force_dynamic_alloc,
+    force_output_dynamic_alloc,
+    no_profiling_on_shape_change,
+    jit_level,
+    comp_node_seq_record_level,
+    graph_opt_level,
+    async_exec_level,
+    enable_nchw44,
+    enable_nchw44_dot,
+    enable_nchw88,
+    enable_nhwcd4,
+    enable_nchw4,
+    enable_nchw32,
+    enable_nchw64,
+);
+
+/// The plain data of [`LiteConfig`], without `bare_model_cryption_name`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct ConfigData {
+    has_compression: i32,
+    device_id: i32,
+    device_type: LiteDeviceType,
+    backend: LiteBackend,
+    options: OptionsData,
+}
+
+impl From<&LiteConfig> for ConfigData {
+    fn from(config: &LiteConfig) -> Self {
+        ConfigData {
+            has_compression: config.has_compression,
+            device_id: config.device_id,
+            device_type: config.device_type,
+            backend: config.backend,
+            options: (&config.options).into(),
+        }
+    }
+}
+
+impl From<ConfigData> for LiteConfig {
+    fn from(config: ConfigData) -> Self {
+        LiteConfig {
+            has_compression: config.has_compression,
+            device_id: config.device_id,
+            device_type: config.device_type,
+            backend: config.backend,
+            bare_model_cryption_name: std::ptr::null_mut(),
+            options: config.options.into(),
+        }
+    }
+}
+
+/// A network to share with, which is not stored as data
+#[derive(Clone, Copy, Debug)]
+enum Share<'a> {
+    RuntimeMemory(&'a Network),
+    Weights(&'a Network),
+}
+
+impl<'a> Share<'a> {
+    fn apply(&self, net: LiteNetwork) {
+        unsafe {
+            match self {
+                Share::RuntimeMemory(src) => api().LITE_share_runtime_memroy(net, src.inner),
+                Share::Weights(src) => api().LITE_shared_weight_with_network(net, src.inner),
+            };
+        }
+    }
+
+    fn same(&self, other: &Share) -> bool {
+        match (self, other) {
+            (Share::RuntimeMemory(a), Share::RuntimeMemory(b)) => std::ptr::eq(*a, *b),
+            (Share::Weights(a), Share::Weights(b)) => std::ptr::eq(*a, *b),
+            _ => false,
+        }
+    }
+}
+
+/// The plain data of [`NetworkBuilder`], without the networks to share with
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Template {
+    config: Option<ConfigData>,
+    options: Vec<NetworkOption>,
+    inputs: Vec<IOConfig>,
+    outputs: Vec<IOConfig>,
+}
+
 /// The network builder
-#[derive(Default)]
+///
+/// The options are stored as data, so the builder can be cloned to build many networks,
+/// compared, and serialized with the feature `serde`. The networks to share with are
+/// compared by address and never serialized, and neither is `bare_model_cryption_name` of
+/// the config.
+#[derive(Default, Clone, Debug)]
 pub struct NetworkBuilder<'a> {
     config: Option<LiteConfig>,
-    option_setting: Vec<Box<dyn FnOnce(LiteNetwork)>>,
-    inputs: Vec<LiteIO>,
-    outputs: Vec<LiteIO>,
-    ccache: Vec<CString>,
-    phantom: std::marker::PhantomData<&'a Network>,
+    options: Vec<NetworkOption>,
+    inputs: Vec<IOConfig>,
+    outputs: Vec<IOConfig>,
+    /// The networks to share with, and the number of options added before each of them
+    shares: Vec<(usize, Share<'a>)>,
 }
 
+// SAFETY: the only member which is not `Send` and `Sync` is the raw pointer
+// `bare_model_cryption_name` of the config, the builder never writes through it, and it
+// is only read by `LITE_make_network` when building, so the builder can be used from
+// any thread like the `&'a Network`s it holds, which are `Send` and `Sync`.
+unsafe impl<'a> Send for NetworkBuilder<'a> {}
+unsafe impl<'a> Sync for NetworkBuilder<'a> {}
+
 impl<'a> NetworkBuilder<'a> {
     /// Set the configration to create the network
     pub fn config(mut self, config: LiteConfig) -> NetworkBuilder<'a> {
@@ -65,188 +308,262 @@ impl<'a> NetworkBuilder<'a> {
 
     /// Set the configration input to create the network
     pub fn add_input(mut self, io: IO) -> NetworkBuilder<'a> {
-        let (cstr, io) = io.as_raw();
-        self.inputs.push(io);
-        self.ccache.push(cstr);
+        self.inputs.push(io.into());
         self
     }
 
     /// Set the configration output to create the network
     pub fn add_output(mut self, io: IO) -> NetworkBuilder<'a> {
-        let (cstr, io) = io.as_raw();
-        self.outputs.push(io);
-        self.ccache.push(cstr);
+        self.outputs.push(io.into());
+        self
+    }
+
+    /// Add an option, the options are set in order before loading the model
+    pub fn option(mut self, option: NetworkOption) -> NetworkBuilder<'a> {
+        self.options.push(option);
         self
     }
 
     /// Set cpu default mode when device is CPU, in some low computation
     /// device or single core device, this mode will get good performace
-    pub fn cpu_inplace(mut self) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(|net| unsafe {
-            api().LITE_set_cpu_inplace_mode(net);
-        }));
-        self
+    pub fn cpu_inplace(self) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::CpuInplace)
     }
 
     /// Enable tensorrt
-    pub fn tensorrt(mut self) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(|net| unsafe {
-            api().LITE_use_tensorrt(net);
-        }));
-        self
+    pub fn tensorrt(self) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::TensorRT)
     }
 
     /// When device is CPU, this interface will set the to be loaded model
     /// run in multi thread mode with the given thread number.
-    pub fn threads_number(mut self, nr_threads: usize) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_set_cpu_threads_number(net, nr_threads);
-        }));
-        self
+    pub fn threads_number(self, nr_threads: usize) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::ThreadsNumber(nr_threads))
     }
 
     /// Set device id, default device id = 0
-    pub fn dev_id(mut self, dev_id: i32) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_set_device_id(net, dev_id);
-        }));
-        self
+    pub fn dev_id(self, dev_id: i32) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::DevId(dev_id))
     }
 
     /// Set stream id, default stream id = 0
-    pub fn stream_id(mut self, stream_id: i32) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_set_stream_id(net, stream_id);
-        }));
-        self
+    pub fn stream_id(self, stream_id: i32) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::StreamId(stream_id))
     }
 
     /// Set opr algorithm selection strategy in the network
-    pub fn algo_policy(mut self, strategy: LiteAlgoSelectStrategy) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_set_network_algo_policy(net, strategy);
-        }));
-        self
+    pub fn algo_policy(self, strategy: LiteAlgoSelectStrategy) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::AlgoPolicy(strategy))
     }
 
     /// Set opr algorithm selection strategy in the network
     pub fn fastrun_config(
-        mut self,
+        self,
         shared_batch_size: u32,
         binary_equal_between_batch: i32,
     ) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_set_network_algo_fastrun_config(
-                net,
-                shared_batch_size,
-                binary_equal_between_batch,
-            );
-        }));
-        self
+        self.option(NetworkOption::FastrunConfig {
+            shared_batch_size,
+            binary_equal_between_batch,
+        })
     }
 
     /// Set workspace_limit for oprs with multiple algorithms, set workspace limit can save memory
     /// but may influence the performance
-    pub fn workspace_limit(mut self, workspace_limit: usize) -> NetworkBuilder<'a> {
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_set_network_algo_workspace_limit(net, workspace_limit);
-        }));
-        self
+    pub fn workspace_limit(self, workspace_limit: usize) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::WorkspaceLimit(workspace_limit))
     }
 
     /// Enable profile the network, a JSON format file will be generated
-    pub fn profile_performance(mut self, path: impl AsRef<Path>) -> NetworkBuilder<'a> {
-        let path_str_c = utils::path_to_cstr(path.as_ref());
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_enable_profile_performance(net, path_str_c.as_ptr());
-        }));
-        self
+    pub fn profile_performance(self, path: impl AsRef<Path>) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::ProfilePerformance(path.as_ref().to_owned()))
     }
 
     /// Dump input/output values of all internal variables to output file
     /// in text format
-    pub fn io_txt_dump(mut self, path: impl AsRef<Path>) -> NetworkBuilder<'a> {
-        let path_str_c = utils::path_to_cstr(path.as_ref());
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_enable_io_txt_dump(net, path_str_c.as_ptr());
-        }));
-        self
+    pub fn io_txt_dump(self, path: impl AsRef<Path>) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::IoTxtDump(path.as_ref().to_owned()))
     }
 
     /// Dump input/output values of all internal variables to output
     /// directory, in binary format
-    pub fn io_bin_dump(mut self, path: impl AsRef<Path>) -> NetworkBuilder<'a> {
-        let path_str_c = utils::path_to_cstr(path.as_ref());
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_enable_io_bin_dump(net, path_str_c.as_ptr());
-        }));
-        self
+    pub fn io_bin_dump(self, path: impl AsRef<Path>) -> NetworkBuilder<'a> {
+        self.option(NetworkOption::IoBinDump(path.as_ref().to_owned()))
     }
 
-    /// Share runtime memory with `net`
+    /// Share runtime memory with `net`, which is set in order with the options
     pub fn share_runtime_memroy(mut self, net: &'a Network) -> NetworkBuilder<'a> {
-        let raw_net = net.inner;
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_share_runtime_memroy(net, raw_net);
-        }));
+        self.shares
+            .push((self.options.len(), Share::RuntimeMemory(net)));
         self
     }
 
-    /// Share weights with `net`
+    /// Share weights with `net`, which is set in order with the options
     pub fn share_weights_with(mut self, net: &'a Network) -> NetworkBuilder<'a> {
-        let raw_net = net.inner;
-        self.option_setting.push(Box::new(move |net| unsafe {
-            api().LITE_shared_weight_with_network(net, raw_net);
-        }));
+        self.shares.push((self.options.len(), Share::Weights(net)));
         self
     }
 
-    /// Load the model to network form given path
-    pub fn build(self, path: impl AsRef<Path>) -> LiteResult<Network> {
-        let path_str_c = utils::path_to_cstr(path.as_ref());
+    /// Get the configration, see also [`NetworkBuilder::config`]
+    pub fn get_config(&self) -> Option<&LiteConfig> {
+        self.config.as_ref()
+    }
+
+    /// Get the options in order
+    pub fn options(&self) -> &[NetworkOption] {
+        &self.options
+    }
+
+    /// Get the configration of inputs
+    pub fn inputs(&self) -> &[IOConfig] {
+        &self.inputs
+    }
+
+    /// Get the configration of outputs
+    pub fn outputs(&self) -> &[IOConfig] {
+        &self.outputs
+    }
+
+    /// A copy of the builder which shares with the networks of `other` after its options
+    pub(crate) fn share_as<'b>(&self, other: &NetworkBuilder<'b>) -> NetworkBuilder<'b>
+    where
+        'a: 'b,
+    {
+        let n = self.options.len();
+        NetworkBuilder {
+            shares: other.shares.iter().map(|&(_, share)| (n, share)).collect(),
+            ..self.clone()
+        }
+    }
+
+    fn template(&self) -> Template {
+        Template {
+            config: self.config.as_ref().map(Into::into),
+            options: self.options.clone(),
+            inputs: self.inputs.clone(),
+            outputs: self.outputs.clone(),
+        }
+    }
+
+    fn make_network(&self) -> LiteResult<LiteNetwork> {
         let config = self.config.unwrap_or(default_config());
+        let (_inputs_name, inputs): (Vec<_>, Vec<_>) =
+            self.inputs.iter().map(IOConfig::as_raw).unzip();
+        let (_outputs_name, outputs): (Vec<_>, Vec<_>) =
+            self.outputs.iter().map(IOConfig::as_raw).unzip();
         let io = LiteNetworkIO {
-            inputs: self.inputs.as_ptr() as *mut LiteIO,
-            outputs: self.outputs.as_ptr() as *mut LiteIO,
-            input_size: self.inputs.len(),
-            output_size: self.outputs.len(),
+            inputs: inputs.as_ptr() as *mut LiteIO,
+            outputs: outputs.as_ptr() as *mut LiteIO,
+            input_size: inputs.len(),
+            output_size: outputs.len(),
         };
 
         let mut net = std::ptr::null_mut();
         unsafe { api().LITE_make_network(&mut net, config, io).into_rst()? };
-        for f in self.option_setting.into_iter() {
-            f(net);
+        for i in 0..=self.options.len() {
+            for (_, share) in self.shares.iter().filter(|(n, _)| *n == i) {
+                share.apply(net);
+            }
+            if let Some(option) = self.options.get(i) {
+                option.apply(net);
+            }
         }
+        Ok(net)
+    }
+
+    /// Load the model to network form given path
+    pub fn build(&self, path: impl AsRef<Path>) -> LiteResult<Network> {
+        let path_str_c = utils::path_to_cstr(path.as_ref());
+        let net = self.make_network()?;
+        // the network is destroyed on error
+        let network = Network::new(net);
         unsafe {
             api()
                 .LITE_load_model_from_path(net, path_str_c.as_ptr())
                 .into_rst()?
         };
-
-        Ok(Network::new(net))
+        Ok(network)
     }
 
     /// Load the model to network form memory
-    pub fn build_from_memory(self, mem: &mut [u8]) -> LiteResult<Network> {
-        let config = self.config.unwrap_or(default_config());
-        let io = LiteNetworkIO {
-            inputs: self.inputs.as_ptr() as *mut LiteIO,
-            outputs: self.outputs.as_ptr() as *mut LiteIO,
-            input_size: self.inputs.len(),
-            output_size: self.outputs.len(),
-        };
-
-        let mut net = std::ptr::null_mut();
-        unsafe { api().LITE_make_network(&mut net, config, io).into_rst()? };
-        for f in self.option_setting.into_iter() {
-            f(net);
-        }
+    pub fn build_from_memory(&self, mem: &mut [u8]) -> LiteResult<Network> {
+        let net = self.make_network()?;
+        let network = Network::new(net);
         unsafe {
             api()
                 .LITE_load_model_from_mem(net, mem.as_ptr() as *mut _, mem.len())
                 .into_rst()?
         };
+        Ok(network)
+    }
+}
+
+impl<'a> PartialEq for NetworkBuilder<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.template() == other.template()
+            && self.shares.len() == other.shares.len()
+            && self
+                .shares
+                .iter()
+                .zip(&other.shares)
+                .all(|((m, a), (n, b))| m == n && a.same(b))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'a> serde::Serialize for NetworkBuilder<'a> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.template().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, 'a> serde::Deserialize<'de> for NetworkBuilder<'a> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let template = Template::deserialize(deserializer)?;
+        Ok(NetworkBuilder {
+            config: template.config.map(Into::into),
+            options: template.options,
+            inputs: template.inputs,
+            outputs: template.outputs,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_builder_template() -> LiteResult<()> {
+        let builder = Network::builder()
+            .dev_id(0)
+            .threads_number(2)
+            .add_input(IO {
+                name: "data",
+                layout: layout!(f32; 1, 3, 224, 224),
+                ..Default::default()
+            });
+        assert_eq!(
+            builder.options(),
+            &[NetworkOption::DevId(0), NetworkOption::ThreadsNumber(2)]
+        );
+        assert_eq!(builder.inputs()[0].shapes, vec![1, 3, 224, 224]);
+        assert_eq!(builder.clone(), builder);
+        assert_ne!(builder.clone().cpu_inplace(), builder);
 
-        Ok(Network::new(net))
+        let a = builder.build(crate::model_path())?;
+        let b = builder
+            .clone()
+            .share_weights_with(&a)
+            .build(crate::model_path())?;
+        assert!(b.io_tensor("data").is_some());
+        // the networks to share with are set in order with the options
+        assert_ne!(
+            Network::builder().share_weights_with(&a).cpu_inplace(),
+            Network::builder().cpu_inplace().share_weights_with(&a)
+        );
+        Ok(())
     }
 }
@@ -0,0 +1,192 @@
+//! The numerical comparison of tensors
+
+use super::*;
+use std::fmt;
+
+/// The report of comparing two tensors element-wise, see also [`compare`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompareReport {
+    /// The number of compared elements
+    pub total: usize,
+    /// The number of elements which are not close
+    pub mismatches: usize,
+    /// The maximum absolute error of finite elements
+    pub max_abs_error: f64,
+    /// The maximum relative error of finite elements, relative to the expected value,
+    /// the elements whose expected value is zero are skipped
+    pub max_rel_error: f64,
+    /// The index of the element with the maximum absolute error, or the first NaN/Inf
+    /// element which is not close
+    pub worst_index: Option<Vec<usize>>,
+    /// The number of elements where either value is NaN
+    pub nan_count: usize,
+    /// The number of elements where either value is infinite
+    pub inf_count: usize,
+}
+
+impl CompareReport {
+    /// Whether all elements are close
+    pub fn is_close(&self) -> bool {
+        self.mismatches == 0
+    }
+}
+
+impl fmt::Display for CompareReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} mismatched, max abs error {:e}, max rel error {:e}",
+            self.mismatches, self.total, self.max_abs_error, self.max_rel_error
+        )?;
+        if let Some(index) = &self.worst_index {
+            write!(f, " at {:?}", index)?;
+        }
+        write!(f, ", {} NaN, {} Inf", self.nan_count, self.inf_count)
+    }
+}
+
+fn unravel(mut i: usize, shape: &[usize]) -> Vec<usize> {
+    let mut index = vec![0; shape.len()];
+    for (x, &n) in index.iter_mut().zip(shape).rev() {
+        *x = i % n;
+        i /= n;
+    }
+    index
+}
+
+/// Compare `actual` with `expected` element-wise, in the NumPy way, an element is close
+/// if `|actual - expected| <= atol + rtol * |expected|`.
+///
+/// The tensors must have the same shape, but may have different data types and devices.
+/// NaN is never close, and infinity is only close to the same infinity.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # fn main() -> LiteResult<()> {
+/// let mut old = Network::builder().build("old_model_path")?;
+/// let mut new = Network::builder().build("new_model_path")?;
+/// old.exec_wait()?;
+/// new.exec_wait()?;
+/// let expected = old.io_tensor("output").unwrap();
+/// let output = new.io_tensor("output").unwrap();
+/// let report = compare(&output, &expected, 1e-5, 1e-6)?;
+/// assert!(report.is_close(), "{}", report);
+/// # Ok(())
+/// # }
+/// ```
+pub fn compare(
+    actual: &Tensor,
+    expected: &Tensor,
+    rtol: f64,
+    atol: f64,
+) -> LiteResult<CompareReport> {
+    if actual.shape() != expected.shape() {
+        return Err(LiteError::InvalidLayout(format!(
+            "cannot compare the tensor of shape {:?} with shape {:?}",
+            actual.shape(),
+            expected.shape()
+        )));
+    }
+    let values = |t: &Tensor| {
+        if t.is_host() {
+            t.to_f64_vec()
+        } else {
+            t.to_host()?.to_f64_vec()
+        }
+    };
+    let (a, b) = (values(actual)?, values(expected)?);
+
+    let mut report = CompareReport {
+        total: a.len(),
+        mismatches: 0,
+        max_abs_error: 0.0,
+        max_rel_error: 0.0,
+        worst_index: None,
+        nan_count: 0,
+        inf_count: 0,
+    };
+    let mut worst = None;
+    let mut first_invalid = None;
+    for (i, (&x, &y)) in a.iter().zip(b.iter()).enumerate() {
+        if x.is_nan() || y.is_nan() {
+            report.nan_count += 1;
+        } else if x.is_infinite() || y.is_infinite() {
+            report.inf_count += 1;
+            if x == y {
+                continue;
+            }
+        } else {
+            let abs = (x - y).abs();
+            // the relative error is undefined if the expected value is zero
+            if y != 0.0 {
+                report.max_rel_error = report.max_rel_error.max(abs / y.abs());
+            }
+            if abs > report.max_abs_error || worst.is_none() {
+                report.max_abs_error = abs;
+                worst = Some(i);
+            }
+            if abs <= atol + rtol * y.abs() {
+                continue;
+            }
+        }
+        report.mismatches += 1;
+        first_invalid = first_invalid.or(Some(i).filter(|_| !(x - y).is_finite()));
+    }
+    report.worst_index = first_invalid
+        .or(worst.filter(|_| report.mismatches > 0 || report.max_abs_error > 0.0))
+        .map(|i| unravel(i, actual.shape()));
+    Ok(report)
+}
+
+impl Tensor {
+    /// Whether all elements of the tensor are close to `other`, see also [`compare`]
+    pub fn allclose(&self, other: &Tensor, rtol: f64, atol: f64) -> LiteResult<bool> {
+        Ok(compare(self, other, rtol, atol)?.is_close())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_compare() -> LiteResult<()> {
+        let a = crate::host_tensor(layout!(f32; 2, 2), &[1.0f32, 2.0, 3.0, 4.0]);
+        let b = crate::host_tensor(layout!(f32; 2, 2), &[1.0f32, 2.5, 3.0, 4.0]);
+        let report = compare(&a, &b, 0.0, 0.1)?;
+        assert!(!report.is_close());
+        assert_eq!(report.mismatches, 1);
+        assert_eq!(report.max_abs_error, 0.5);
+        assert_eq!(report.max_rel_error, 0.2);
+        assert_eq!(report.worst_index, Some(vec![0, 1]));
+        assert!(a.allclose(&b, 0.0, 0.5)?);
+        assert!(a.allclose(&b, 0.2, 0.0)?);
+        assert!(a.allclose(&a, 0.0, 0.0)?);
+        assert_eq!(compare(&a, &a, 0.0, 0.0)?.worst_index, None);
+
+        let c = crate::host_tensor(layout!(i32; 2, 2), &[1, 2, 3, 4]);
+        assert!(a.allclose(&c, 0.0, 0.0)?);
+
+        let z = crate::host_tensor(layout!(f32; 2, 2), &[0.0f32, 2.0, 3.0, 4.0]);
+        let report = compare(&a, &z, 0.0, 0.0)?;
+        assert_eq!(report.mismatches, 1);
+        assert_eq!(report.max_abs_error, 1.0);
+        assert_eq!(report.max_rel_error, 0.0);
+
+        let d = crate::host_tensor(layout!(f32; 2, 2), &[f32::NAN, 2.0, f32::INFINITY, 4.0]);
+        let e = crate::host_tensor(layout!(f32; 2, 2), &[1.0f32, 2.0, f32::INFINITY, 4.0]);
+        let report = compare(&d, &e, 0.0, 0.0)?;
+        assert_eq!(report.mismatches, 1);
+        assert_eq!(report.nan_count, 1);
+        assert_eq!(report.inf_count, 1);
+        assert_eq!(report.worst_index, Some(vec![0, 0]));
+
+        let f = crate::host_tensor(layout!(f32; 4), &[1.0f32, 2.0, 3.0, 4.0]);
+        assert!(matches!(
+            a.allclose(&f, 0.0, 0.0),
+            Err(LiteError::InvalidLayout(_))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,291 @@
+//! The formatting of tensor and network
+
+use super::*;
+use std::fmt;
+
+/// The options to print the values of tensor, see also [`Tensor::display_with`]
+#[derive(Debug, Clone, Copy)]
+pub struct PrintOptions {
+    /// The number of digits after the decimal point for float values, it is
+    /// overridden by the precision of formatter, e.g. `{:.2}`
+    pub precision: usize,
+    /// The number of items printed at the beginning and end of each dimension
+    /// when the tensor is summarized
+    pub edge_items: usize,
+    /// The tensor is summarized if the number of elements exceeds `threshold`
+    pub threshold: usize,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            precision: 4,
+            edge_items: 3,
+            threshold: 1000,
+        }
+    }
+}
+
+/// A helper to display the values of tensor, see also [`Tensor::display_with`]
+pub struct TensorDisplay<'a> {
+    tensor: &'a Tensor,
+    options: PrintOptions,
+}
+
+impl Tensor {
+    /// Display the values of a host tensor in NumPy style with `options`
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// let mut t = Tensor::host().unwrap();
+    /// t.set_layout(layout!(f32; 100, 100));
+    /// let options = PrintOptions {
+    ///     edge_items: 2,
+    ///     ..Default::default()
+    /// };
+    /// println!("{:.2}", t.display_with(options));
+    /// ```
+    pub fn display_with(&self, options: PrintOptions) -> TensorDisplay {
+        TensorDisplay {
+            tensor: self,
+            options,
+        }
+    }
+}
+
+struct Printer<'a> {
+    tensor: &'a Tensor,
+    shape: &'a [usize],
+    strides: Vec<usize>,
+    edge_items: Option<usize>,
+    precision: usize,
+}
+
+impl<'a> Printer<'a> {
+    /// The indices of dimension `dim` to print, `None` is the ellipsis
+    fn indices(&self, dim: usize) -> Vec<Option<usize>> {
+        let n = self.shape[dim];
+        match self.edge_items {
+            Some(edge) if n > 2 * edge => (0..edge)
+                .map(Some)
+                .chain(std::iter::once(None))
+                .chain((n - edge..n).map(Some))
+                .collect(),
+            _ => (0..n).map(Some).collect(),
+        }
+    }
+
+    fn element(&self, offset: usize) -> String {
+        let p = self.tensor.as_ptr::<u8>();
+        let width = DataType::width(self.tensor.dtype());
+        unsafe {
+            let p = p.add(offset * width);
+            match self.tensor.dtype() {
+                DataType::F32 => format!("{:.*}", self.precision, *(p as *const f32)),
+                DataType::F16 => {
+                    let x = utils::f16_to_f32(*(p as *const u16));
+                    format!("{:.*}", self.precision, x)
+                }
+                DataType::I32 => (*(p as *const i32)).to_string(),
+                DataType::I16 => (*(p as *const i16)).to_string(),
+                DataType::I8 => (*(p as *const i8)).to_string(),
+                DataType::U32 => (*(p as *const u32)).to_string(),
+                DataType::U16 => (*(p as *const u16)).to_string(),
+                DataType::U8 => (*p).to_string(),
+                DataType::I64 => (*(p as *const i64)).to_string(),
+                _ => "?".to_owned(),
+            }
+        }
+    }
+
+    fn collect(&self, dim: usize, offset: usize, cells: &mut Vec<String>) {
+        if dim == self.shape.len() {
+            cells.push(self.element(offset));
+            return;
+        }
+        for i in self.indices(dim).into_iter().flatten() {
+            self.collect(dim + 1, offset + i * self.strides[dim], cells);
+        }
+    }
+
+    fn write(
+        &self,
+        f: &mut fmt::Formatter,
+        dim: usize,
+        cells: &mut std::slice::Iter<String>,
+        width: usize,
+    ) -> fmt::Result {
+        if dim == self.shape.len() {
+            return write!(f, "{:>1$}", cells.next().unwrap(), width);
+        }
+        f.write_str("[")?;
+        for (k, i) in self.indices(dim).into_iter().enumerate() {
+            if k > 0 {
+                if dim + 1 == self.shape.len() {
+                    f.write_str(", ")?;
+                } else {
+                    f.write_str(",")?;
+                    for _ in dim + 1..self.shape.len() {
+                        f.write_str("\n")?;
+                    }
+                    write!(f, "{:1$}", "", dim + 1)?;
+                }
+            }
+            match i {
+                Some(_) => self.write(f, dim + 1, cells, width)?,
+                None => f.write_str("...")?,
+            }
+        }
+        f.write_str("]")
+    }
+}
+
+impl<'a> fmt::Display for TensorDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tensor = self.tensor;
+        if !tensor.is_host() {
+            return write!(
+                f,
+                "Tensor(shape={:?}, dtype={}, device={}:{})",
+                tensor.shape(),
+                DataType::name(tensor.dtype()),
+                DeviceType::name(tensor.dev_type()),
+                tensor.dev_id()
+            );
+        }
+        let shape = tensor.shape();
+        // a tensor without layout has no element, and its memory is not allocated
+        if shape.is_empty() {
+            return f.write_str("[]");
+        }
+        let total: usize = shape.iter().product();
+        let printer = Printer {
+            tensor,
+            shape,
+            strides: tensor.strides(),
+            edge_items: Some(self.options.edge_items).filter(|_| total > self.options.threshold),
+            precision: f.precision().unwrap_or(self.options.precision),
+        };
+        let mut cells = vec![];
+        if total > 0 {
+            printer.collect(0, 0, &mut cells);
+        }
+        let width = cells.iter().map(|x| x.len()).max().unwrap_or(0);
+        printer.write(f, 0, &mut cells.iter(), width)
+    }
+}
+
+impl fmt::Display for Tensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.display_with(PrintOptions::default()), f)
+    }
+}
+
+impl fmt::Debug for Tensor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tensor")
+            .field("dtype", &DataType::name(self.dtype()))
+            .field("shape", &self.shape())
+            .field("device_type", &DeviceType::name(self.dev_type()))
+            .field("device_id", &self.dev_id())
+            .field("is_pinned_host", &self.is_pinned_host())
+            .field("is_continue", &self.is_continue())
+            .field("nbytes", &self.nbytes())
+            .finish()
+    }
+}
+
+struct IoList<'a> {
+    network: &'a Network,
+    names: Vec<&'a str>,
+}
+
+impl<'a> fmt::Debug for IoList<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let layouts = self.names.iter().map(|&name| {
+            let layout = self.network.io_tensor(name).map(|t| {
+                format!(
+                    "{}{:?} on {}:{}",
+                    DataType::name(t.dtype()),
+                    t.shape(),
+                    DeviceType::name(t.dev_type()),
+                    t.dev_id()
+                )
+            });
+            (name, layout.unwrap_or_default())
+        });
+        f.debug_map().entries(layouts).finish()
+    }
+}
+
+impl fmt::Debug for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Network")
+            .field(
+                "inputs",
+                &IoList {
+                    network: self,
+                    names: self.input_names(),
+                },
+            )
+            .field(
+                "outputs",
+                &IoList {
+                    network: self,
+                    names: self.output_names(),
+                },
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    fn arange(layout: Layout) -> Tensor {
+        let mut tensor = Tensor::host().unwrap();
+        tensor.set_layout(layout);
+        for (i, x) in tensor.as_slice_mut::<f32>().unwrap().iter_mut().enumerate() {
+            *x = i as f32;
+        }
+        tensor
+    }
+
+    #[test]
+    fn test_display() {
+        let tensor = arange(layout!(f32; 2, 3));
+        assert_eq!(
+            format!("{:.1}", tensor),
+            "[[0.0, 1.0, 2.0],\n [3.0, 4.0, 5.0]]"
+        );
+        let sub = tensor.slice(idx![.., 1..;2]).unwrap();
+        assert_eq!(format!("{:.0}", sub), "[[1],\n [4]]");
+
+        let tensor = arange(layout!(f32; 2, 1, 2));
+        assert_eq!(format!("{:.0}", tensor), "[[[0, 1]],\n\n [[2, 3]]]");
+
+        let tensor = arange(layout!(f32; 20));
+        let options = PrintOptions {
+            precision: 0,
+            edge_items: 2,
+            threshold: 10,
+        };
+        assert_eq!(
+            tensor.display_with(options).to_string(),
+            "[ 0,  1, ..., 18, 19]"
+        );
+
+        assert_eq!(Tensor::host().unwrap().to_string(), "[]");
+    }
+
+    #[test]
+    fn test_debug() {
+        let tensor = arange(layout!(f32; 2, 3));
+        let s = format!("{:?}", tensor);
+        assert!(s.contains("dtype: \"f32\""));
+        assert!(s.contains("shape: [2, 3]"));
+        assert!(s.contains("nbytes: 24"));
+    }
+}
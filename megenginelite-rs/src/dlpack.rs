@@ -0,0 +1,308 @@
+//! The [DLPack](https://github.com/dmlc/dlpack) support, to exchange tensors with other
+//! frameworks without copy
+
+use super::*;
+use crate::ffi::*;
+use std::ffi::c_void;
+use std::sync::Arc;
+
+/// The device type code of DLPack
+pub type DLDeviceType = i32;
+pub const DL_CPU: DLDeviceType = 1;
+pub const DL_CUDA: DLDeviceType = 2;
+pub const DL_CUDA_HOST: DLDeviceType = 3;
+
+/// The data type code of DLPack
+pub const DL_INT: u8 = 0;
+pub const DL_UINT: u8 = 1;
+pub const DL_FLOAT: u8 = 2;
+
+/// The device of a [`DLTensor`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DLDevice {
+    pub device_type: DLDeviceType,
+    pub device_id: i32,
+}
+
+/// The data type of a [`DLTensor`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DLDataType {
+    pub code: u8,
+    pub bits: u8,
+    pub lanes: u16,
+}
+
+/// The plain tensor of DLPack, the `shape` and `strides` are in elements
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLTensor {
+    pub data: *mut c_void,
+    pub device: DLDevice,
+    pub ndim: i32,
+    pub dtype: DLDataType,
+    pub shape: *mut i64,
+    pub strides: *mut i64,
+    pub byte_offset: u64,
+}
+
+/// The managed tensor of DLPack, the consumer calls `deleter` to release it
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLManagedTensor {
+    pub dltensor: DLTensor,
+    pub manager_ctx: *mut c_void,
+    pub deleter: Option<unsafe extern "C" fn(*mut DLManagedTensor)>,
+}
+
+fn dl_device(tensor: &Tensor) -> LiteResult<DLDevice> {
+    let device_type = match tensor.dev_type() {
+        DeviceType::CUDA if tensor.is_pinned_host() => DL_CUDA_HOST,
+        _ if tensor.is_host() => DL_CPU,
+        DeviceType::CUDA => DL_CUDA,
+        ty => {
+            return Err(LiteError::InvalidDevice(format!(
+                "device {} is not supported by DLPack",
+                DeviceType::name(ty)
+            )))
+        }
+    };
+    Ok(DLDevice {
+        device_type,
+        device_id: tensor.dev_id(),
+    })
+}
+
+fn dl_data_type(ty: LiteDataType) -> LiteResult<DLDataType> {
+    let (code, bits) = match ty {
+        DataType::F32 => (DL_FLOAT, 32),
+        DataType::F16 => (DL_FLOAT, 16),
+        DataType::I32 => (DL_INT, 32),
+        DataType::I16 => (DL_INT, 16),
+        DataType::I8 => (DL_INT, 8),
+        DataType::U32 => (DL_UINT, 32),
+        DataType::U16 => (DL_UINT, 16),
+        DataType::U8 => (DL_UINT, 8),
+        DataType::I64 => (DL_INT, 64),
+        _ => {
+            return Err(LiteError::InvalidLayout(format!(
+                "data type {} is not supported by DLPack",
+                ty
+            )))
+        }
+    };
+    Ok(DLDataType {
+        code,
+        bits,
+        lanes: 1,
+    })
+}
+
+fn data_type(dtype: DLDataType) -> LiteResult<LiteDataType> {
+    Ok(match (dtype.code, dtype.bits, dtype.lanes) {
+        (DL_FLOAT, 32, 1) => DataType::F32,
+        (DL_FLOAT, 16, 1) => DataType::F16,
+        (DL_INT, 32, 1) => DataType::I32,
+        (DL_INT, 16, 1) => DataType::I16,
+        (DL_INT, 8, 1) => DataType::I8,
+        (DL_UINT, 32, 1) => DataType::U32,
+        (DL_UINT, 16, 1) => DataType::U16,
+        (DL_UINT, 8, 1) => DataType::U8,
+        (DL_INT, 64, 1) => DataType::I64,
+        _ => {
+            return Err(LiteError::InvalidLayout(format!(
+                "DLPack data type {:?} is not supported",
+                dtype
+            )))
+        }
+    })
+}
+
+/// The context of an exported tensor, which keeps the tensor alive
+struct ExportContext {
+    tensor: Tensor,
+    shape: Vec<i64>,
+    strides: Vec<i64>,
+}
+
+unsafe extern "C" fn export_deleter(managed: *mut DLManagedTensor) {
+    if managed.is_null() {
+        return;
+    }
+    let managed = Box::from_raw(managed);
+    drop(Box::from_raw(managed.manager_ctx as *mut ExportContext));
+}
+
+/// The owner of an imported tensor, which calls the deleter of the producer on drop
+struct ImportOwner(*mut DLManagedTensor);
+
+unsafe impl Send for ImportOwner {}
+unsafe impl Sync for ImportOwner {}
+
+impl Drop for ImportOwner {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(deleter) = (*self.0).deleter {
+                deleter(self.0);
+            }
+        }
+    }
+}
+
+impl Tensor {
+    /// Export the tensor as a DLPack managed tensor without copy, the tensor is kept
+    /// alive until the consumer calls the deleter.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let mut network = Network::builder().build("model_path")?;
+    /// network.exec_wait()?;
+    /// let output = network.io_tensor("output").unwrap();
+    /// let managed = output.to_dlpack()?;
+    /// // pass `managed` to other frameworks, e.g. in a PyCapsule named "dltensor"
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_dlpack(self) -> LiteResult<*mut DLManagedTensor> {
+        let device = dl_device(&self)?;
+        let dtype = dl_data_type(self.dtype())?;
+        let shape = self.shape();
+        let mut strides = self.strides();
+        // the stride of a dimension with size less than 2 is arbitrary, use the packed one
+        for i in (0..shape.len()).rev() {
+            if shape[i] < 2 {
+                strides[i] = if i + 1 < shape.len() {
+                    strides[i + 1] * shape[i + 1].max(1)
+                } else {
+                    1
+                };
+            }
+        }
+        let mut ctx = Box::new(ExportContext {
+            shape: shape.iter().map(|&x| x as i64).collect(),
+            strides: strides.into_iter().map(|x| x as i64).collect(),
+            tensor: self,
+        });
+        let dltensor = DLTensor {
+            data: ctx.tensor.as_ptr::<c_void>() as *mut _,
+            device,
+            ndim: ctx.shape.len() as i32,
+            dtype,
+            shape: ctx.shape.as_mut_ptr(),
+            strides: ctx.strides.as_mut_ptr(),
+            byte_offset: 0,
+        };
+        Ok(Box::into_raw(Box::new(DLManagedTensor {
+            dltensor,
+            manager_ctx: Box::into_raw(ctx) as *mut _,
+            deleter: Some(export_deleter),
+        })))
+    }
+
+    /// Import a DLPack managed tensor without copy, the deleter of `managed` is called
+    /// when the tensor and its views are dropped, or if the import fails.
+    ///
+    /// The memory of `managed` must be continue.
+    ///
+    /// # Safety
+    /// `managed` must be a valid DLPack managed tensor, and its ownership is taken.
+    pub unsafe fn from_dlpack(managed: *mut DLManagedTensor) -> LiteResult<Tensor> {
+        let owner = Arc::new(ImportOwner(managed));
+        let dl = &(*managed).dltensor;
+
+        let data_type = data_type(dl.dtype)?;
+        let ndim = dl.ndim as usize;
+        if ndim > LAYOUT_MAX_DIM as usize {
+            return Err(LiteError::InvalidLayout(format!(
+                "DLPack tensor has {} dims, but the maximum dim is {}",
+                ndim, LAYOUT_MAX_DIM
+            )));
+        }
+        let mut shape: Vec<usize> = if ndim == 0 {
+            vec![]
+        } else {
+            std::slice::from_raw_parts(dl.shape, ndim)
+                .iter()
+                .map(|&x| x as usize)
+                .collect()
+        };
+        if !dl.strides.is_null() && ndim > 0 {
+            let strides = std::slice::from_raw_parts(dl.strides, ndim);
+            let mut packed = 1;
+            for i in (0..ndim).rev() {
+                if shape[i] > 1 && strides[i] != packed {
+                    return Err(LiteError::InvalidLayout(
+                        "DLPack tensor with non-continue memory is not supported".into(),
+                    ));
+                }
+                packed *= shape[i] as i64;
+            }
+        }
+        // a scalar is imported as a tensor of shape (1,)
+        if shape.is_empty() {
+            shape.push(1);
+        }
+
+        let DLDevice {
+            device_type,
+            device_id,
+        } = dl.device;
+        let mut tensor = match device_type {
+            DL_CPU => Tensor::host()?,
+            DL_CUDA => Tensor::device(DeviceType::CUDA, device_id)?,
+            DL_CUDA_HOST => Tensor::pinned_host(DeviceType::CUDA, device_id)?,
+            ty => {
+                return Err(LiteError::InvalidDevice(format!(
+                    "DLPack device type {} is not supported",
+                    ty
+                )))
+            }
+        };
+        tensor.set_layout(Layout {
+            shapes: &shape,
+            data_type,
+        });
+        let nbytes = shape.iter().product::<usize>() * DataType::width(data_type);
+        if nbytes > 0 {
+            let p = (dl.data as *mut u8).add(dl.byte_offset as usize);
+            tensor.reset_memory(p as *mut _, nbytes, owner)?;
+        }
+        Ok(tensor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_dlpack() -> LiteResult<()> {
+        let mut tensor = Tensor::host()?;
+        tensor.set_layout(layout!(f32; 2, 3));
+        tensor
+            .as_slice_mut::<f32>()?
+            .copy_from_slice(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let p = tensor.as_ptr::<f32>();
+
+        let managed = tensor.to_dlpack()?;
+        unsafe {
+            let dl = &(*managed).dltensor;
+            assert_eq!(dl.device.device_type, DL_CPU);
+            assert_eq!(dl.ndim, 2);
+            assert_eq!(std::slice::from_raw_parts(dl.shape, 2), &[2, 3]);
+            assert_eq!(std::slice::from_raw_parts(dl.strides, 2), &[3, 1]);
+            assert_eq!(dl.data as *const f32, p);
+        }
+
+        let tensor = unsafe { Tensor::from_dlpack(managed)? };
+        assert_eq!(tensor.shape(), &[2, 3]);
+        assert_eq!(tensor.as_ptr::<f32>(), p);
+        let view = tensor.slice(idx![1])?;
+        drop(tensor);
+        assert_eq!(view.as_slice::<f32>()?, &[4.0, 5.0, 6.0]);
+        Ok(())
+    }
+}
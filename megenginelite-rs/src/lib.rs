@@ -26,8 +26,9 @@ let mut network = Network::builder()
 
 // get an input of the model by name
 let mut input = network.io_tensor("input_name").unwrap();
-let data = Tensor::host()?;
-input.copy_from(&data);
+let mut data = input.to_host()?;
+// fill the data ...
+input.copy_from(&data)?;
 
 // exec, and wait
 network.exec_wait()?;
@@ -36,7 +37,7 @@ network.exec().await?;
 
 // get an output of the model by name
 let output = network.io_tensor("output_name").unwrap();
-println!("{:?}", output.as_slice::<f32>());
+println!("{:?}", output.as_slice::<f32>()?);
 # Ok(())
 # }
 ```
@@ -50,6 +51,10 @@ The following features are turned on by default:
 The following features is optional.
 
 - `ndarray-basis`: enable ndarray support.
+- `npy`: enable NumPy `.npy` and `.npz` support.
+- `safetensors-basis`: enable safetensors support.
+- `metrics`: report the metrics of `TensorPool` by the `metrics` crate.
+- `serde`: serialize and deserialize `NetworkBuilder` by the `serde` crate.
 - `ndarray-rayon`: enable ndarray/rayon feature.
 
 */
@@ -57,19 +62,46 @@ The following features is optional.
 extern crate self as megenginelite_rs;
 
 mod api;
+mod arena;
+mod batcher;
+mod buffer;
 mod builder;
+mod compare;
+mod display;
+mod dlpack;
 mod global;
 mod network;
+mod network_pool;
+#[cfg(feature = "npy")]
+mod npy;
+mod ops;
+mod pipeline;
 mod pool;
+#[cfg(feature = "safetensors-basis")]
+mod safetensors;
+mod stream;
 mod tensor;
 mod types;
 mod utils;
 
+#[cfg(feature = "safetensors-basis")]
+pub use crate::safetensors::*;
 pub use api::*;
+pub use arena::*;
+pub use batcher::*;
+pub use buffer::*;
 pub use builder::*;
+pub use compare::*;
+pub use display::*;
+pub use dlpack::*;
 pub use global::*;
 pub use network::*;
+pub use network_pool::*;
+#[cfg(feature = "npy")]
+pub use npy::*;
+pub use pipeline::*;
 pub use pool::*;
+pub use stream::*;
 pub use tensor::*;
 pub use types::*;
 
@@ -85,3 +117,11 @@ fn model_path() -> std::path::PathBuf {
     path.push("../resources/shufflenet.mge");
     path
 }
+
+#[cfg(test)]
+fn host_tensor<T: Copy>(layout: Layout, data: &[T]) -> Tensor {
+    let mut tensor = Tensor::host().unwrap();
+    tensor.set_layout(layout);
+    tensor.as_slice_mut::<T>().unwrap().copy_from_slice(data);
+    tensor
+}
@@ -1,6 +1,8 @@
 //! The network module
 
-use super::{api, IntoLiteRst, LiteResult, NetworkBuilder, Tensor};
+use super::{
+    api, DataType, DeviceType, IntoLiteRst, LiteError, LiteResult, NetworkBuilder, Tensor,
+};
 use crate::ffi::*;
 use atomic_waker::AtomicWaker;
 use std::ffi::{CStr, CString};
@@ -79,34 +81,90 @@ impl Network {
     pub fn io_tensor(&self, name: &str) -> Option<Tensor> {
         let name = CString::new(name).unwrap();
         let mut tensor = std::ptr::null_mut();
-        let mut desc;
-
         unsafe {
-            desc = LiteTensorDesc {
-                is_pinned_host: 0,
-                layout: Tensor::default_layout(),
-                device_type: LiteDeviceType_LITE_CPU,
-                device_id: 0,
-            };
             api().LITE_get_io_tensor(
                 self.inner,
                 name.as_ptr(),
                 LiteTensorPhase_LITE_IO,
                 &mut tensor,
             );
-            api().LITE_is_pinned_host(tensor, &mut desc.is_pinned_host);
-            api().LITE_get_tensor_device_type(tensor, &mut desc.device_type);
-            api().LITE_get_tensor_layout(tensor, &mut desc.layout);
-            api().LITE_get_tensor_device_id(tensor, &mut desc.device_id);
         }
 
         if tensor.is_null() {
             None
         } else {
-            Some(Tensor::new(tensor, desc))
+            Some(Tensor::from_raw(tensor))
         }
     }
 
+    /// Share the memory of `slot` with the input `name` for one forward without copy, the
+    /// original memory of the input is restored when the returned binding is dropped.
+    ///
+    /// The `slot` is usually a [`crate::PoolSlot`], it is reshaped to the shape of the
+    /// input, so they must have the same data type, device and number of elements.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let mut network = Network::builder().build("model_path")?;
+    /// let pool = TensorPool::host(layout!(f32; 8, 1, 3, 224, 224))?;
+    /// let slot = pool.get_blocking()?;
+    /// // fill the slot ...
+    /// network.bind_input("data", &slot)?.exec_wait()?;
+    /// let output = network.io_tensor("output").unwrap();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bind_input<'n, 'p>(
+        &'n mut self,
+        name: &str,
+        slot: &'p Tensor,
+    ) -> LiteResult<InputBinding<'n, 'p>> {
+        let mut input = self
+            .io_tensor(name)
+            .ok_or_else(|| LiteError::MGELiteError(format!("no input named {}", name)))?;
+        if slot.dtype() != input.dtype() {
+            return Err(LiteError::InvalidLayout(format!(
+                "the data type of slot {} mismatches the input {}",
+                DataType::name(slot.dtype()),
+                DataType::name(input.dtype())
+            )));
+        }
+        if slot.is_host() != input.is_host() {
+            return Err(LiteError::InvalidDevice(
+                "the slot and the input must be both on host or device".into(),
+            ));
+        }
+        let same_device = if input.is_host() {
+            slot.is_pinned_host() || slot.dev_id() == input.dev_id()
+        } else {
+            slot.dev_type() == input.dev_type() && slot.dev_id() == input.dev_id()
+        };
+        if !same_device {
+            return Err(LiteError::InvalidDevice(format!(
+                "the slot on {}:{} mismatches the input on {}:{}",
+                DeviceType::name(slot.dev_type()),
+                slot.dev_id(),
+                DeviceType::name(input.dev_type()),
+                input.dev_id()
+            )));
+        }
+        let mut view = slot.view()?;
+        let shape: Vec<_> = input.shape().iter().map(|&x| x as i32).collect();
+        view.reshape(&shape)?;
+
+        let saved = input.view()?;
+        input.borrow_from(&view)?;
+        Ok(InputBinding {
+            network: self,
+            input,
+            saved,
+            _slot: slot,
+            running: false,
+        })
+    }
+
     /// Get the input tensor name in the order in loaded model
     pub fn input_names(&self) -> Vec<&str> {
         let mut n = 0;
@@ -150,6 +208,43 @@ impl Network {
     }
 }
 
+/// An input of the network bound to a slot, see also [`Network::bind_input`]
+///
+/// The slot is kept checked out until the forward completes, even if the future of
+/// [`InputBinding::exec`] is dropped.
+pub struct InputBinding<'n, 'p> {
+    network: &'n mut Network,
+    input: Tensor,
+    saved: Tensor,
+    _slot: &'p Tensor,
+    /// Whether an async forward may be running
+    running: bool,
+}
+
+impl<'n, 'p> InputBinding<'n, 'p> {
+    /// Forward the network with the bound input, see also [`Network::exec_wait`]
+    pub fn exec_wait(self) -> LiteResult<()> {
+        self.network.exec_wait()
+    }
+
+    /// Async version of `exec_wait`
+    pub async fn exec(mut self) -> LiteResult<()> {
+        self.running = true;
+        let rst = self.network.exec().await;
+        self.running = false;
+        rst
+    }
+}
+
+impl<'n, 'p> Drop for InputBinding<'n, 'p> {
+    fn drop(&mut self) {
+        if self.running {
+            unsafe { api().LITE_wait(self.network.inner) };
+        }
+        self.input.borrow_from(&self.saved).ok();
+    }
+}
+
 #[doc(hidden)]
 #[derive(Default, Clone)]
 pub struct AsyncExec {
@@ -229,6 +324,35 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_bind_input() -> LiteResult<()> {
+        let mut network = Network::builder().build(model_path())?;
+        let p = network.io_tensor("data").unwrap().as_ptr::<u8>();
+        let pool = TensorPool::host(layout!(f32; 2, 3, 224, 224))?;
+        let slot = pool.get_blocking()?;
+        let binding = network.bind_input("data", &slot)?;
+        assert_eq!(
+            binding.network.io_tensor("data").unwrap().as_ptr::<u8>(),
+            slot.as_ptr::<u8>()
+        );
+        binding.exec_wait()?;
+        let input = network.io_tensor("data").unwrap();
+        assert_eq!(input.as_ptr::<u8>(), p);
+        assert_eq!(input.shape(), &[1, 3, 224, 224]);
+
+        let other = pool.get_blocking()?;
+        assert!(network.bind_input("data", &other).is_ok());
+        assert!(network.bind_input("none", &other).is_err());
+
+        let mut slot = Tensor::device(DeviceType::CPU, 1)?;
+        slot.set_layout(layout!(f32; 1, 3, 224, 224));
+        assert!(matches!(
+            network.bind_input("data", &slot),
+            Err(LiteError::InvalidDevice(_))
+        ));
+        Ok(())
+    }
+
     #[test]
     fn test_io() -> LiteResult<()> {
         let mut network = Network::builder().build(model_path())?;
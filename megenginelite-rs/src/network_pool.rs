@@ -0,0 +1,309 @@
+//! The pool of network instances for concurrent inference
+
+use super::pool::FreeList;
+use super::{api, utils, Idx, LiteError, LiteResult, Network, NetworkBuilder, PoolStats};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::Mutex;
+
+type Factory = dyn for<'a> Fn(NetworkBuilder<'a>) -> LiteResult<Network> + Send + Sync;
+
+/// A pool of network instances which share the weights, so the requests can be executed
+/// concurrently
+///
+/// The instances are built from a template builder or a factory, sharing the weights with
+/// a source network, which holds the weights and is never executed. An
+/// instance is rebuilt if its execution reports an error.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # #[tokio::main]
+/// # async fn main() -> LiteResult<()> {
+/// let pool = NetworkPool::from_builder(4, Network::builder().dev_id(0), "model_path")?;
+/// let mut network = pool.get().await?;
+/// let mut input = network.io_tensor("data").unwrap();
+/// // fill the input ...
+/// network.exec().await?;
+/// let output = network.io_tensor("output").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub struct NetworkPool {
+    // the instances are dropped before the source network
+    networks: Vec<Mutex<Option<Network>>>,
+    source: Network,
+    share_runtime_memory: bool,
+    factory: Box<Factory>,
+    freelist: FreeList,
+    /// The single turn to check out an instance if the runtime memory is shared
+    turn: Option<FreeList>,
+}
+
+impl NetworkPool {
+    /// Create a pool of `n` instances built by `factory`
+    pub fn new<F>(n: usize, factory: F) -> LiteResult<Self>
+    where
+        F: for<'a> Fn(NetworkBuilder<'a>) -> LiteResult<Network> + Send + Sync + 'static,
+    {
+        Self::with_options(n, false, Box::new(factory))
+    }
+
+    /// Create a pool of `n` instances built by `factory`, which also share the runtime
+    /// memory with the source network.
+    ///
+    /// It saves memory, but the instances sharing the runtime memory must not be executed
+    /// at the same time, so only one instance is checked out at a time.
+    pub fn with_shared_runtime_memory<F>(n: usize, factory: F) -> LiteResult<Self>
+    where
+        F: for<'a> Fn(NetworkBuilder<'a>) -> LiteResult<Network> + Send + Sync + 'static,
+    {
+        Self::with_options(n, true, Box::new(factory))
+    }
+
+    /// Create a pool of `n` instances built by the template `builder` from the model at
+    /// `path`, the networks to share with set in `builder` are ignored.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let builder = Network::builder().dev_id(0).threads_number(2);
+    /// let pool = NetworkPool::from_builder(4, builder, "model_path")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_builder(
+        n: usize,
+        builder: NetworkBuilder<'static>,
+        path: impl AsRef<Path>,
+    ) -> LiteResult<Self> {
+        let path = path.as_ref().to_owned();
+        Self::new(n, move |shared| builder.share_as(&shared).build(&path))
+    }
+
+    fn with_options(
+        n: usize,
+        share_runtime_memory: bool,
+        factory: Box<Factory>,
+    ) -> LiteResult<Self> {
+        if n == 0 {
+            return Err(LiteError::MGELiteError(
+                "the network pool needs at least one instance".into(),
+            ));
+        }
+        let source = factory(Network::builder())?;
+        let mut pool = NetworkPool {
+            networks: Vec::with_capacity(n),
+            source,
+            share_runtime_memory,
+            factory,
+            freelist: FreeList::new(n),
+            turn: share_runtime_memory.then(|| FreeList::new(1)),
+        };
+        for _ in 0..n {
+            let network = pool.build()?;
+            pool.networks.push(Mutex::new(Some(network)));
+        }
+        Ok(pool)
+    }
+
+    fn build(&self) -> LiteResult<Network> {
+        let mut builder = Network::builder().share_weights_with(&self.source);
+        if self.share_runtime_memory {
+            builder = builder.share_runtime_memroy(&self.source);
+        }
+        (self.factory)(builder)
+    }
+
+    /// The number of instances
+    pub fn len(&self) -> usize {
+        self.networks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    /// Return the number of free instances in pool
+    pub fn free_n(&self) -> usize {
+        self.freelist.len()
+    }
+
+    /// Request an instance, will wait if all instances are in use
+    ///
+    /// Return an error if the pool is closed, see also [`NetworkPool::close`]
+    pub async fn get(&self) -> LiteResult<PooledNetwork<'_>> {
+        let turn = match &self.turn {
+            Some(turn) => Some(turn.pop().await?),
+            None => None,
+        };
+        Ok(self.checkout(self.freelist.pop().await?, turn))
+    }
+
+    /// Request an instance, will block the current thread if all instances are in use,
+    /// see also [`NetworkPool::get`]
+    pub fn get_blocking(&self) -> LiteResult<PooledNetwork<'_>> {
+        utils::block_on(self.get(), None).unwrap()
+    }
+
+    /// Request an instance, return `None` if all instances are in use or the pool is
+    /// closed
+    pub fn try_get(&self) -> Option<PooledNetwork<'_>> {
+        let turn = match &self.turn {
+            Some(turn) => Some(turn.try_pop()?),
+            None => None,
+        };
+        Some(self.checkout(self.freelist.try_pop()?, turn))
+    }
+
+    /// Get a snapshot of the statistics
+    pub fn stats(&self) -> PoolStats {
+        self.freelist.stats()
+    }
+
+    /// Close the pool, all waiting and later requests return an error
+    pub fn close(&self) {
+        self.freelist.close();
+        if let Some(turn) = &self.turn {
+            turn.close();
+        }
+    }
+
+    /// Whether the pool is closed
+    pub fn is_closed(&self) -> bool {
+        self.freelist.is_closed()
+    }
+
+    fn slot(&self, idx: &Idx) -> std::sync::MutexGuard<'_, Option<Network>> {
+        // never block because the instance at `idx` is owned by `idx`
+        self.networks[idx.get()]
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn checkout(&self, idx: Idx, turn: Option<Idx>) -> PooledNetwork<'_> {
+        PooledNetwork {
+            network: self.slot(&idx).take(),
+            idx,
+            _turn: turn,
+            pool: self,
+            running: false,
+        }
+    }
+}
+
+/// A checked-out instance of the [`NetworkPool`], which derefs to the network, the
+/// instance is given back when it is dropped.
+pub struct PooledNetwork<'a> {
+    // always `Some` until dropped
+    network: Option<Network>,
+    idx: Idx,
+    _turn: Option<Idx>,
+    pool: &'a NetworkPool,
+    /// Whether an async forward may be running
+    running: bool,
+}
+
+impl<'a> PooledNetwork<'a> {
+    /// Get the index of the instance
+    pub fn idx(&self) -> &Idx {
+        &self.idx
+    }
+
+    /// Forward the network and wait until finish, see also [`Network::exec_wait`]
+    ///
+    /// The instance is rebuilt if an error is reported.
+    pub fn exec_wait(&mut self) -> LiteResult<()> {
+        let rst = self.deref_mut().exec_wait();
+        self.check(rst)
+    }
+
+    /// Async version of [`PooledNetwork::exec_wait`]
+    pub async fn exec(&mut self) -> LiteResult<()> {
+        self.running = true;
+        let rst = self.deref_mut().exec().await;
+        self.running = false;
+        self.check(rst)
+    }
+
+    /// Rebuild the instance, the io tensors got before are not tied to the new instance
+    pub fn rebuild(&mut self) -> LiteResult<()> {
+        self.network = Some(self.pool.build()?);
+        Ok(())
+    }
+
+    fn check(&mut self, rst: LiteResult<()>) -> LiteResult<()> {
+        if rst.is_err() {
+            // the error of the execution is more useful than the error of rebuilding
+            self.rebuild().ok();
+        }
+        rst
+    }
+}
+
+impl<'a> Deref for PooledNetwork<'a> {
+    type Target = Network;
+    fn deref(&self) -> &Network {
+        self.network.as_ref().unwrap()
+    }
+}
+
+impl<'a> DerefMut for PooledNetwork<'a> {
+    fn deref_mut(&mut self) -> &mut Network {
+        self.network.as_mut().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledNetwork<'a> {
+    fn drop(&mut self) {
+        // the future of `exec` is dropped, so wait before the instance is reused
+        if let (true, Some(network)) = (self.running, &self.network) {
+            unsafe { api().LITE_wait(network.inner) };
+        }
+        // the instance is given back before the index
+        *self.pool.slot(&self.idx) = self.network.take();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_network_pool() -> LiteResult<()> {
+        let pool = std::sync::Arc::new(NetworkPool::from_builder(
+            2,
+            Network::builder(),
+            crate::model_path(),
+        )?);
+        assert_eq!(pool.len(), 2);
+        let mut a = pool.get_blocking()?;
+        let b = pool.try_get().unwrap();
+        assert_ne!(a.idx().get(), b.idx().get());
+        assert!(pool.try_get().is_none());
+        a.exec_wait()?;
+
+        let handle = {
+            let pool = pool.clone();
+            std::thread::spawn(move || pool.get_blocking()?.exec_wait())
+        };
+        drop(b);
+        handle.join().unwrap()?;
+        drop(a);
+        assert_eq!(pool.free_n(), 2);
+        assert_eq!(pool.stats().acquisitions, 3);
+
+        let pool = NetworkPool::new(1, |builder| builder.build(crate::model_path()))?;
+        pool.get_blocking()?.exec_wait()?;
+
+        let pool = NetworkPool::with_shared_runtime_memory(2, |b| b.build(crate::model_path()))?;
+        let a = pool.try_get().unwrap();
+        assert!(pool.try_get().is_none());
+        drop(a);
+        pool.try_get().unwrap().exec_wait()?;
+
+        assert!(NetworkPool::new(0, |b| b.build(crate::model_path())).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,471 @@
+//! The NumPy `.npy` and `.npz` format support
+
+use super::*;
+use crate::ffi::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"\x93NUMPY";
+/// The max length of the header, which is checked before the header is read
+const MAX_HEADER_LEN: usize = 1 << 16;
+
+fn descr(ty: LiteDataType) -> LiteResult<&'static str> {
+    Ok(match ty {
+        DataType::F32 => "f4",
+        DataType::F16 => "f2",
+        DataType::I32 => "i4",
+        DataType::I16 => "i2",
+        DataType::I8 => "i1",
+        DataType::U32 => "u4",
+        DataType::U16 => "u2",
+        DataType::U8 => "u1",
+        DataType::I64 => "i8",
+        _ => {
+            return Err(LiteError::InvalidFormat(format!(
+                "data type {} is not supported by npy",
+                ty
+            )))
+        }
+    })
+}
+
+fn data_type(descr: &str) -> LiteResult<LiteDataType> {
+    Ok(match descr {
+        "f4" => DataType::F32,
+        "f2" => DataType::F16,
+        "i4" => DataType::I32,
+        "i2" => DataType::I16,
+        "i1" => DataType::I8,
+        "u4" => DataType::U32,
+        "u2" => DataType::U16,
+        "u1" => DataType::U8,
+        "i8" => DataType::I64,
+        _ => {
+            return Err(LiteError::InvalidFormat(format!(
+                "npy descr {} is not supported",
+                descr
+            )))
+        }
+    })
+}
+
+#[derive(Debug, PartialEq)]
+struct Header {
+    data_type: LiteDataType,
+    big_endian: bool,
+    fortran_order: bool,
+    shape: Vec<usize>,
+}
+
+/// Return the text after `'key':`
+fn field<'a>(dict: &'a str, key: &str) -> LiteResult<&'a str> {
+    let pattern = format!("'{}'", key);
+    dict.find(&pattern)
+        .and_then(|i| dict[i + pattern.len()..].trim_start().strip_prefix(':'))
+        .map(|x| x.trim_start())
+        .ok_or_else(|| LiteError::InvalidFormat(format!("npy header has no field {}", key)))
+}
+
+impl Header {
+    fn parse(dict: &str) -> LiteResult<Header> {
+        let dict = dict.replace('"', "'");
+        let invalid =
+            |key: &str| LiteError::InvalidFormat(format!("npy header field {} is invalid", key));
+
+        let descr = field(&dict, "descr")?
+            .strip_prefix('\'')
+            .and_then(|x| x.split('\'').next())
+            .ok_or_else(|| invalid("descr"))?;
+        let (order, descr) = descr.split_at(descr.len().min(1));
+        let big_endian = match order {
+            "<" | "|" => false,
+            ">" => true,
+            "=" => cfg!(target_endian = "big"),
+            _ => return Err(invalid("descr")),
+        };
+
+        let fortran_order = field(&dict, "fortran_order")?;
+        let fortran_order = if fortran_order.starts_with("True") {
+            true
+        } else if fortran_order.starts_with("False") {
+            false
+        } else {
+            return Err(invalid("fortran_order"));
+        };
+
+        let shape = field(&dict, "shape")?
+            .strip_prefix('(')
+            .and_then(|x| x.split(')').next())
+            .ok_or_else(|| invalid("shape"))?;
+        let shape = shape
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse().map_err(|_| invalid("shape")))
+            .collect::<LiteResult<Vec<usize>>>()?;
+
+        Ok(Header {
+            data_type: data_type(descr)?,
+            big_endian,
+            fortran_order,
+            shape,
+        })
+    }
+
+    fn to_bytes(&self) -> LiteResult<Vec<u8>> {
+        let order = if cfg!(target_endian = "big") {
+            '>'
+        } else {
+            '<'
+        };
+        let shape = match self.shape.len() {
+            1 => format!("({},)", self.shape[0]),
+            _ => format!(
+                "({})",
+                self.shape
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let mut dict = format!(
+            "{{'descr': '{}{}', 'fortran_order': False, 'shape': {}, }}",
+            order,
+            descr(self.data_type)?,
+            shape
+        );
+
+        // the total length of header is aligned to 64 bytes, and it ends with '\n'
+        let prefix = if dict.len() + 64 < u16::MAX as usize {
+            10
+        } else {
+            12
+        };
+        let len = dict.len() + 1 + prefix;
+        dict.push_str(&" ".repeat((64 - len % 64) % 64));
+        dict.push('\n');
+
+        let mut bytes = MAGIC.to_vec();
+        if prefix == 10 {
+            bytes.extend([1, 0]);
+            bytes.extend((dict.len() as u16).to_le_bytes());
+        } else {
+            bytes.extend([2, 0]);
+            bytes.extend((dict.len() as u32).to_le_bytes());
+        }
+        bytes.extend(dict.as_bytes());
+        Ok(bytes)
+    }
+}
+
+fn read_header(r: &mut impl Read) -> LiteResult<Header> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic[..6] != MAGIC {
+        return Err(LiteError::InvalidFormat("not a npy file".into()));
+    }
+    let len = match magic[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            r.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            r.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        v => {
+            return Err(LiteError::InvalidFormat(format!(
+                "npy version {} is not supported",
+                v
+            )))
+        }
+    };
+    if len > MAX_HEADER_LEN {
+        return Err(LiteError::InvalidFormat(format!(
+            "npy header of {} bytes is too long",
+            len
+        )));
+    }
+    let mut dict = vec![0u8; len];
+    r.read_exact(&mut dict)?;
+    let dict = String::from_utf8(dict)
+        .map_err(|_| LiteError::InvalidFormat("npy header is not utf8".into()))?;
+    Header::parse(&dict)
+}
+
+impl Tensor {
+    /// Write the tensor to `w` in the NumPy `.npy` format, device tensors are copied
+    /// to host first.
+    pub fn write_npy(&self, mut w: impl Write) -> LiteResult<()> {
+        let packed = self.packed_host()?;
+        let tensor = packed.as_ref().unwrap_or(self);
+        let header = Header {
+            data_type: tensor.dtype(),
+            big_endian: cfg!(target_endian = "big"),
+            fortran_order: false,
+            shape: tensor.shape().to_vec(),
+        };
+        w.write_all(&header.to_bytes()?)?;
+        w.write_all(tensor.as_slice::<u8>()?)?;
+        Ok(())
+    }
+
+    /// Save the tensor to a NumPy `.npy` file, see also [`Tensor::write_npy`]
+    pub fn save_npy(&self, path: impl AsRef<Path>) -> LiteResult<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_npy(&mut w)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Read a host tensor from `r` in the NumPy `.npy` format, the data in fortran
+    /// order or another byte order is converted.
+    pub fn read_npy(mut r: impl Read) -> LiteResult<Tensor> {
+        let header = read_header(&mut r)?;
+        if header.shape.len() > LAYOUT_MAX_DIM as usize {
+            return Err(LiteError::InvalidFormat(format!(
+                "npy array has {} dims, but the maximum dim is {}",
+                header.shape.len(),
+                LAYOUT_MAX_DIM
+            )));
+        }
+        // a scalar is loaded as a tensor of shape (1,)
+        let shape = if header.shape.is_empty() {
+            vec![1]
+        } else {
+            header.shape
+        };
+        let mut tensor = Tensor::host()?;
+        tensor.set_layout(Layout {
+            shapes: &shape,
+            data_type: header.data_type,
+        });
+
+        let width = DataType::width(header.data_type);
+        let dst = tensor.as_slice_mut::<u8>()?;
+        if header.fortran_order {
+            let mut strides = vec![1; shape.len()];
+            for i in 1..shape.len() {
+                strides[i] = strides[i - 1] * shape[i - 1];
+            }
+            let mut src = vec![0u8; dst.len()];
+            r.read_exact(&mut src)?;
+            for (i, offset) in Offsets::new(&shape, &strides).enumerate() {
+                dst[i * width..(i + 1) * width]
+                    .copy_from_slice(&src[offset * width..(offset + 1) * width]);
+            }
+        } else {
+            r.read_exact(dst)?;
+        }
+        if header.big_endian != cfg!(target_endian = "big") {
+            dst.chunks_exact_mut(width).for_each(|x| x.reverse());
+        }
+        Ok(tensor)
+    }
+
+    /// Load a host tensor from a NumPy `.npy` file, see also [`Tensor::read_npy`]
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let network = Network::builder().build("model_path")?;
+    /// let mut input = network.io_tensor("data").unwrap();
+    /// input.copy_from(&Tensor::load_npy("data.npy")?)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn load_npy(path: impl AsRef<Path>) -> LiteResult<Tensor> {
+        Tensor::read_npy(BufReader::new(File::open(path)?))
+    }
+}
+
+impl From<zip::result::ZipError> for LiteError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => LiteError::IOError(e),
+            e => LiteError::InvalidFormat(e.to_string()),
+        }
+    }
+}
+
+/// A reader of the NumPy `.npz` archive
+pub struct NpzReader<R: Read + Seek> {
+    archive: zip::ZipArchive<R>,
+}
+
+impl NpzReader<BufReader<File>> {
+    /// Open a `.npz` file
+    pub fn open(path: impl AsRef<Path>) -> LiteResult<Self> {
+        NpzReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> NpzReader<R> {
+    pub fn new(r: R) -> LiteResult<Self> {
+        Ok(NpzReader {
+            archive: zip::ZipArchive::new(r)?,
+        })
+    }
+
+    /// The names of arrays in the archive
+    pub fn names(&self) -> Vec<&str> {
+        self.archive
+            .file_names()
+            .map(|x| x.strip_suffix(".npy").unwrap_or(x))
+            .collect()
+    }
+
+    /// Read the array `name` as a host tensor
+    pub fn by_name(&mut self, name: &str) -> LiteResult<Tensor> {
+        let file = self.archive.by_name(&format!("{}.npy", name))?;
+        Tensor::read_npy(file)
+    }
+}
+
+/// A writer of the NumPy `.npz` archive, the arrays are stored without compression
+/// as `numpy.savez`.
+pub struct NpzWriter<W: Write + Seek> {
+    zip: zip::ZipWriter<W>,
+}
+
+impl NpzWriter<BufWriter<File>> {
+    /// Create a `.npz` file
+    pub fn create(path: impl AsRef<Path>) -> LiteResult<Self> {
+        Ok(NpzWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write + Seek> NpzWriter<W> {
+    pub fn new(w: W) -> Self {
+        NpzWriter {
+            zip: zip::ZipWriter::new(w),
+        }
+    }
+
+    /// Add the `tensor` to the archive with `name`
+    pub fn add(&mut self, name: &str, tensor: &Tensor) -> LiteResult<()> {
+        let options = zip::write::FileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored)
+            .large_file(tensor.nbytes() >= u32::MAX as usize);
+        self.zip.start_file(format!("{}.npy", name), options)?;
+        tensor.write_npy(&mut self.zip)
+    }
+
+    /// Finish the archive and return the inner writer
+    pub fn finish(mut self) -> LiteResult<W> {
+        Ok(self.zip.finish()?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_header() {
+        let header =
+            Header::parse("{'descr': '<f4', 'fortran_order': False, 'shape': (1, 3, 224, 224), }")
+                .unwrap();
+        assert_eq!(header.data_type, DataType::F32);
+        assert!(!header.big_endian);
+        assert!(!header.fortran_order);
+        assert_eq!(header.shape, &[1, 3, 224, 224]);
+
+        let header =
+            Header::parse("{'descr': '>i8', 'fortran_order': True, 'shape': (5,), }").unwrap();
+        assert_eq!(header.data_type, DataType::I64);
+        assert!(header.big_endian);
+        assert!(header.fortran_order);
+        assert_eq!(header.shape, &[5]);
+
+        assert!(Header::parse("{'descr': '<f8', 'fortran_order': False, 'shape': (), }").is_err());
+        assert!(Header::parse("{'descr': '<f4', 'shape': (), }").is_err());
+
+        let bytes = Header {
+            data_type: DataType::U8,
+            big_endian: false,
+            fortran_order: false,
+            shape: vec![2, 3],
+        }
+        .to_bytes()
+        .unwrap();
+        assert_eq!(bytes.len() % 64, 0);
+        assert_eq!(*bytes.last().unwrap(), b'\n');
+        let header = read_header(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(header.shape, &[2, 3]);
+        assert_eq!(header.data_type, DataType::U8);
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend([2, 0]);
+        bytes.extend(u32::MAX.to_le_bytes());
+        assert!(matches!(
+            read_header(&mut Cursor::new(bytes)),
+            Err(LiteError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_npy() -> LiteResult<()> {
+        let mut tensor = Tensor::host()?;
+        tensor.set_layout(layout!(i16; 2, 3));
+        for (i, x) in tensor.as_slice_mut::<i16>()?.iter_mut().enumerate() {
+            *x = i as i16;
+        }
+        let mut buf = vec![];
+        tensor.write_npy(&mut buf)?;
+        let other = Tensor::read_npy(Cursor::new(&buf))?;
+        assert_eq!(other.shape(), &[2, 3]);
+        assert_eq!(other.dtype(), DataType::I16);
+        assert_eq!(other.as_slice::<i16>()?, tensor.as_slice::<i16>()?);
+
+        // the array [[0, 1, 2], [3, 4, 5]] in fortran order
+        let mut buf = Header {
+            data_type: DataType::U8,
+            big_endian: false,
+            fortran_order: false,
+            shape: vec![2, 3],
+        }
+        .to_bytes()?;
+        let s = String::from_utf8(buf.clone()).unwrap();
+        let s = s.replace("'fortran_order': False", "'fortran_order': True ");
+        buf = s.into_bytes();
+        buf.extend([0, 3, 1, 4, 2, 5]);
+        let other = Tensor::read_npy(Cursor::new(&buf))?;
+        assert_eq!(other.as_slice::<u8>()?, &[0, 1, 2, 3, 4, 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_npz() -> LiteResult<()> {
+        let mut a = Tensor::host()?;
+        a.set_layout(layout!(f32; 4));
+        a.as_slice_mut::<f32>()?
+            .copy_from_slice(&[1.0, 2.0, 3.0, 4.0]);
+        let mut b = Tensor::host()?;
+        b.set_layout(layout!(u8; 2, 2));
+        b.fill_zero();
+
+        let mut writer = NpzWriter::new(Cursor::new(vec![]));
+        writer.add("a", &a)?;
+        writer.add("b", &b)?;
+        let buf = writer.finish()?.into_inner();
+
+        let mut reader = NpzReader::new(Cursor::new(buf))?;
+        let mut names = reader.names();
+        names.sort_unstable();
+        assert_eq!(names, &["a", "b"]);
+        assert_eq!(
+            reader.by_name("a")?.as_slice::<f32>()?,
+            &[1.0, 2.0, 3.0, 4.0]
+        );
+        assert_eq!(reader.by_name("b")?.shape(), &[2, 2]);
+        assert!(reader.by_name("c").is_err());
+        Ok(())
+    }
+}
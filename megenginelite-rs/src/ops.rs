@@ -0,0 +1,466 @@
+//! The host element-wise math and reductions of tensor
+//!
+//! The values of floats are computed in `f64` and the values of integers in `i128`, the
+//! conversion to the data type of the result saturates (e.g. integer division by zero).
+
+use super::*;
+use crate::ffi::*;
+
+/// The type in which the values are computed
+trait Value: Copy {
+    unsafe fn read(p: *const u8, ty: LiteDataType, i: usize) -> Self;
+    unsafe fn write(p: *mut u8, ty: LiteDataType, i: usize, x: Self);
+}
+
+impl Value for f64 {
+    unsafe fn read(p: *const u8, ty: LiteDataType, i: usize) -> f64 {
+        match ty {
+            DataType::F32 => *(p as *const f32).add(i) as f64,
+            DataType::F16 => utils::f16_to_f32(*(p as *const u16).add(i)) as f64,
+            DataType::I32 => *(p as *const i32).add(i) as f64,
+            DataType::I16 => *(p as *const i16).add(i) as f64,
+            DataType::I8 => *(p as *const i8).add(i) as f64,
+            DataType::U32 => *(p as *const u32).add(i) as f64,
+            DataType::U16 => *(p as *const u16).add(i) as f64,
+            DataType::U8 => *p.add(i) as f64,
+            DataType::I64 => *(p as *const i64).add(i) as f64,
+            _ => unreachable!(),
+        }
+    }
+
+    unsafe fn write(p: *mut u8, ty: LiteDataType, i: usize, x: f64) {
+        match ty {
+            DataType::F32 => *(p as *mut f32).add(i) = x as f32,
+            DataType::F16 => *(p as *mut u16).add(i) = utils::f32_to_f16(x as f32),
+            DataType::I32 => *(p as *mut i32).add(i) = x as i32,
+            DataType::I16 => *(p as *mut i16).add(i) = x as i16,
+            DataType::I8 => *(p as *mut i8).add(i) = x as i8,
+            DataType::U32 => *(p as *mut u32).add(i) = x as u32,
+            DataType::U16 => *(p as *mut u16).add(i) = x as u16,
+            DataType::U8 => *p.add(i) = x as u8,
+            DataType::I64 => *(p as *mut i64).add(i) = x as i64,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Value for i128 {
+    unsafe fn read(p: *const u8, ty: LiteDataType, i: usize) -> i128 {
+        match ty {
+            DataType::I32 => *(p as *const i32).add(i) as i128,
+            DataType::I16 => *(p as *const i16).add(i) as i128,
+            DataType::I8 => *(p as *const i8).add(i) as i128,
+            DataType::U32 => *(p as *const u32).add(i) as i128,
+            DataType::U16 => *(p as *const u16).add(i) as i128,
+            DataType::U8 => *p.add(i) as i128,
+            DataType::I64 => *(p as *const i64).add(i) as i128,
+            _ => f64::read(p, ty, i) as i128,
+        }
+    }
+
+    unsafe fn write(p: *mut u8, ty: LiteDataType, i: usize, x: i128) {
+        macro_rules! sat {
+            ($t:ty) => {
+                x.clamp(<$t>::MIN as i128, <$t>::MAX as i128) as $t
+            };
+        }
+        match ty {
+            DataType::I32 => *(p as *mut i32).add(i) = sat!(i32),
+            DataType::I16 => *(p as *mut i16).add(i) = sat!(i16),
+            DataType::I8 => *(p as *mut i8).add(i) = sat!(i8),
+            DataType::U32 => *(p as *mut u32).add(i) = sat!(u32),
+            DataType::U16 => *(p as *mut u16).add(i) = sat!(u16),
+            DataType::U8 => *p.add(i) = sat!(u8),
+            DataType::I64 => *(p as *mut i64).add(i) = sat!(i64),
+            _ => f64::write(p, ty, i, x as f64),
+        }
+    }
+}
+
+fn is_float(ty: LiteDataType) -> bool {
+    ty == DataType::F32 || ty == DataType::F16
+}
+
+/// The shape of the broadcast result of `a` and `b`, in the NumPy way
+fn broadcast_shape(a: &[usize], b: &[usize]) -> LiteResult<Vec<usize>> {
+    let ndim = a.len().max(b.len());
+    (0..ndim)
+        .map(|i| {
+            let x = if i + a.len() >= ndim {
+                a[i + a.len() - ndim]
+            } else {
+                1
+            };
+            let y = if i + b.len() >= ndim {
+                b[i + b.len() - ndim]
+            } else {
+                1
+            };
+            match (x, y) {
+                (x, y) if x == y => Ok(x),
+                (1, y) => Ok(y),
+                (x, 1) => Ok(x),
+                _ => Err(LiteError::InvalidLayout(format!(
+                    "shape {:?} cannot be broadcast with shape {:?}",
+                    a, b
+                ))),
+            }
+        })
+        .collect()
+}
+
+/// The strides to read `tensor` as the broadcast shape `shape`
+fn broadcast_strides(tensor: &Tensor, shape: &[usize]) -> Vec<usize> {
+    let pad = shape.len() - tensor.shape().len();
+    let strides = tensor.strides();
+    (0..shape.len())
+        .map(|i| match i.checked_sub(pad) {
+            Some(j) if tensor.shape()[j] != 1 => strides[j],
+            _ => 0,
+        })
+        .collect()
+}
+
+impl Tensor {
+    /// The values of a host tensor in the logical order
+    pub(crate) fn to_f64_vec(&self) -> LiteResult<Vec<f64>> {
+        self.to_vec()
+    }
+
+    fn to_vec<V: Value>(&self) -> LiteResult<Vec<V>> {
+        self.check_host()?;
+        let p = self.as_ptr::<u8>();
+        let ty = self.dtype();
+        Ok(Offsets::new(self.shape(), &self.strides())
+            .map(|offset| unsafe { V::read(p, ty, offset) })
+            .collect())
+    }
+
+    /// A new host tensor of `shape` and `ty` with the values in the logical order
+    fn from_values<V: Value>(
+        shape: &[usize],
+        ty: LiteDataType,
+        values: impl IntoIterator<Item = V>,
+    ) -> LiteResult<Tensor> {
+        let mut tensor = Tensor::host()?;
+        tensor.set_layout(Layout {
+            shapes: shape,
+            data_type: ty,
+        });
+        let total: usize = shape.iter().product();
+        let p = tensor.as_ptr_mut::<u8>();
+        for (i, x) in values.into_iter().take(total).enumerate() {
+            unsafe { V::write(p, ty, i, x) };
+        }
+        Ok(tensor)
+    }
+
+    fn binary(
+        &self,
+        other: &Tensor,
+        f: impl Fn(f64, f64) -> f64,
+        g: impl Fn(i128, i128) -> i128,
+    ) -> LiteResult<Tensor> {
+        self.check_host()?;
+        other.check_host()?;
+        if self.dtype() != other.dtype() {
+            return Err(LiteError::InvalidLayout(format!(
+                "data type {} mismatches {}",
+                DataType::name(self.dtype()),
+                DataType::name(other.dtype())
+            )));
+        }
+        if is_float(self.dtype()) {
+            self.broadcast(other, f)
+        } else {
+            self.broadcast(other, g)
+        }
+    }
+
+    fn broadcast<V: Value>(&self, other: &Tensor, f: impl Fn(V, V) -> V) -> LiteResult<Tensor> {
+        let ty = self.dtype();
+        let shape = broadcast_shape(self.shape(), other.shape())?;
+        let (pa, pb) = (self.as_ptr::<u8>(), other.as_ptr::<u8>());
+        let a = Offsets::new(&shape, &broadcast_strides(self, &shape));
+        let b = Offsets::new(&shape, &broadcast_strides(other, &shape));
+        let values = a
+            .zip(b)
+            .map(|(i, j)| unsafe { f(V::read(pa, ty, i), V::read(pb, ty, j)) });
+        Tensor::from_values(&shape, ty, values)
+    }
+
+    /// Element-wise `self + other` of host tensors with the same data type, the shapes
+    /// are broadcast in the NumPy way.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let mut network = Network::builder().build("model_path")?;
+    /// network.exec_wait()?;
+    /// let output = network.io_tensor("output").unwrap().to_host()?;
+    /// let mut bias = Tensor::host()?;
+    /// bias.set_layout(layout!(f32; output.shape()[1], 1, 1));
+    /// let output = output.add(&bias)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add(&self, other: &Tensor) -> LiteResult<Tensor> {
+        self.binary(other, |a, b| a + b, |a, b| a + b)
+    }
+
+    /// Element-wise `self - other`, see also [`Tensor::add`]
+    pub fn sub(&self, other: &Tensor) -> LiteResult<Tensor> {
+        self.binary(other, |a, b| a - b, |a, b| a - b)
+    }
+
+    /// Element-wise `self * other`, see also [`Tensor::add`]
+    pub fn mul(&self, other: &Tensor) -> LiteResult<Tensor> {
+        self.binary(other, |a, b| a * b, |a, b| a * b)
+    }
+
+    /// Element-wise `self / other`, see also [`Tensor::add`]
+    ///
+    /// The division of integers truncates toward zero.
+    pub fn div(&self, other: &Tensor) -> LiteResult<Tensor> {
+        self.binary(
+            other,
+            |a, b| a / b,
+            |a, b| match b {
+                0 => a.signum() * i128::MAX,
+                b => a / b,
+            },
+        )
+    }
+
+    /// Convert a host tensor to the data type `ty`
+    ///
+    /// see also [`crate::DataType`], which is the alias of `LiteDataType`
+    pub fn cast(&self, ty: LiteDataType) -> LiteResult<Tensor> {
+        if is_float(self.dtype()) || is_float(ty) {
+            Tensor::from_values(self.shape(), ty, self.to_vec::<f64>()?)
+        } else {
+            Tensor::from_values(self.shape(), ty, self.to_vec::<i128>()?)
+        }
+    }
+
+    /// Clamp all values of a host tensor into the range `[min, max]`
+    pub fn clamp(&self, min: f64, max: f64) -> LiteResult<Tensor> {
+        if is_float(self.dtype()) {
+            let values = self.to_vec::<f64>()?;
+            let values = values.into_iter().map(|x| x.max(min).min(max));
+            Tensor::from_values(self.shape(), self.dtype(), values)
+        } else {
+            let (min, max) = (min.ceil() as i128, max.floor() as i128);
+            let values = self.to_vec::<i128>()?;
+            let values = values.into_iter().map(|x| x.max(min).min(max));
+            Tensor::from_values(self.shape(), self.dtype(), values)
+        }
+    }
+
+    /// Reduce the values of a host tensor along `axis` by `f`, the result is in the
+    /// shape without `axis` (or `[1]` if no dim is left).
+    fn reduce<V: Value, W: Value>(
+        &self,
+        axis: usize,
+        ty: LiteDataType,
+        f: impl Fn(&mut dyn Iterator<Item = V>) -> W,
+    ) -> LiteResult<Tensor> {
+        let shape = self.shape();
+        if axis >= shape.len() {
+            return Err(LiteError::InvalidLayout(format!(
+                "axis {} is out of range for the tensor of {} dims",
+                axis,
+                shape.len()
+            )));
+        }
+        let values = self.to_vec::<V>()?;
+        let n = shape[axis];
+        let inner: usize = shape[axis + 1..].iter().product();
+        let outer: usize = shape[..axis].iter().product();
+
+        let mut new_shape = shape.to_vec();
+        new_shape.remove(axis);
+        if new_shape.is_empty() {
+            new_shape.push(1);
+        }
+        let result = (0..outer * inner).map(|i| {
+            let (o, i) = (i / inner, i % inner);
+            let base = o * n * inner + i;
+            f(&mut (0..n).map(|k| values[base + k * inner]))
+        });
+        Tensor::from_values(&new_shape, ty, result)
+    }
+
+    fn check_nonempty_axis(&self, axis: usize) -> LiteResult<()> {
+        match self.shape().get(axis) {
+            Some(0) => Err(LiteError::InvalidLayout(format!(
+                "cannot reduce the empty axis {}",
+                axis
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sum a host tensor along `axis`, integers are summed exactly into `i64`, which
+    /// saturates on overflow.
+    pub fn sum(&self, axis: usize) -> LiteResult<Tensor> {
+        if is_float(self.dtype()) {
+            self.reduce(axis, self.dtype(), |it: &mut dyn Iterator<Item = f64>| {
+                it.sum::<f64>()
+            })
+        } else {
+            self.reduce(axis, DataType::I64, |it: &mut dyn Iterator<Item = i128>| {
+                it.sum::<i128>()
+            })
+        }
+    }
+
+    /// The mean of a host tensor along `axis`, the mean of integers is `f32`.
+    pub fn mean(&self, axis: usize) -> LiteResult<Tensor> {
+        self.check_nonempty_axis(axis)?;
+        let n = self.shape().get(axis).copied().unwrap_or(1) as f64;
+        if is_float(self.dtype()) {
+            self.reduce(axis, self.dtype(), |it: &mut dyn Iterator<Item = f64>| {
+                it.sum::<f64>() / n
+            })
+        } else {
+            self.reduce(axis, DataType::F32, |it: &mut dyn Iterator<Item = i128>| {
+                it.sum::<i128>() as f64 / n
+            })
+        }
+    }
+
+    /// The maximum of a host tensor along `axis`, NaN is propagated.
+    pub fn max(&self, axis: usize) -> LiteResult<Tensor> {
+        self.check_nonempty_axis(axis)?;
+        if is_float(self.dtype()) {
+            self.reduce(axis, self.dtype(), |it: &mut dyn Iterator<Item = f64>| {
+                it.reduce(|a, b| if a.is_nan() || a >= b { a } else { b })
+                    .unwrap()
+            })
+        } else {
+            self.reduce(axis, self.dtype(), |it: &mut dyn Iterator<Item = i128>| {
+                it.max().unwrap()
+            })
+        }
+    }
+
+    /// The index of the maximum of a host tensor along `axis` in `i32`, the first one
+    /// is returned if there are multiple maximums, or NaN.
+    pub fn argmax(&self, axis: usize) -> LiteResult<Tensor> {
+        self.check_nonempty_axis(axis)?;
+        if is_float(self.dtype()) {
+            self.reduce(axis, DataType::I32, |it: &mut dyn Iterator<Item = f64>| {
+                let mut best = (0, f64::NEG_INFINITY);
+                for (i, x) in it.enumerate() {
+                    if best.1.is_nan() {
+                        break;
+                    }
+                    if i == 0 || x.is_nan() || x > best.1 {
+                        best = (i, x);
+                    }
+                }
+                best.0 as i128
+            })
+        } else {
+            self.reduce(axis, DataType::I32, |it: &mut dyn Iterator<Item = i128>| {
+                let mut best = (0, i128::MIN);
+                for (i, x) in it.enumerate() {
+                    if i == 0 || x > best.1 {
+                        best = (i, x);
+                    }
+                }
+                best.0 as i128
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_binary() -> LiteResult<()> {
+        let a = crate::host_tensor(layout!(f32; 2, 3), &[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let b = crate::host_tensor(layout!(f32; 3), &[1.0f32, 2.0, 4.0]);
+        let c = crate::host_tensor(layout!(f32; 2, 1), &[10.0f32, 20.0]);
+        assert_eq!(
+            a.add(&b)?.as_slice::<f32>()?,
+            &[2.0, 4.0, 7.0, 5.0, 7.0, 10.0]
+        );
+        assert_eq!(
+            a.sub(&c)?.as_slice::<f32>()?,
+            &[-9.0, -8.0, -7.0, -16.0, -15.0, -14.0]
+        );
+        let d = b.mul(&c)?;
+        assert_eq!(d.shape(), &[2, 3]);
+        assert_eq!(d.as_slice::<f32>()?, &[10.0, 20.0, 40.0, 20.0, 40.0, 80.0]);
+        assert_eq!(
+            a.div(&b)?.as_slice::<f32>()?,
+            &[1.0, 1.0, 0.75, 4.0, 2.5, 1.5]
+        );
+
+        let x = crate::host_tensor(layout!(i32; 3), &[7, -7, 1]);
+        let y = crate::host_tensor(layout!(i32; 3), &[2, 2, 0]);
+        assert_eq!(x.div(&y)?.as_slice::<i32>()?, &[3, -3, i32::MAX]);
+
+        let big = (1i64 << 53) + 1;
+        let x = crate::host_tensor(layout!(i64; 2), &[big, i64::MAX]);
+        let y = crate::host_tensor(layout!(i64; 2), &[1, 1]);
+        assert_eq!(x.add(&y)?.as_slice::<i64>()?, &[big + 1, i64::MAX]);
+        assert_eq!(x.sub(&y)?.as_slice::<i64>()?, &[big - 1, i64::MAX - 1]);
+
+        assert!(matches!(a.add(&x), Err(LiteError::InvalidLayout(_))));
+        let e = crate::host_tensor(layout!(f32; 2), &[1.0f32, 2.0]);
+        assert!(matches!(a.add(&e), Err(LiteError::InvalidLayout(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cast_clamp() -> LiteResult<()> {
+        let a = crate::host_tensor(layout!(f32; 4), &[-1.5f32, 0.5, 2.5, 300.0]);
+        assert_eq!(a.cast(DataType::U8)?.as_slice::<u8>()?, &[0, 0, 2, 255]);
+        assert_eq!(a.cast(DataType::I32)?.as_slice::<i32>()?, &[-1, 0, 2, 300]);
+        let h = a.cast(DataType::F16)?;
+        assert_eq!(h.as_slice::<u16>()?, &[0xbe00, 0x3800, 0x4100, 0x5cb0]);
+        assert_eq!(
+            h.cast(DataType::F32)?.as_slice::<f32>()?,
+            a.as_slice::<f32>()?
+        );
+        assert_eq!(a.clamp(0.0, 1.0)?.as_slice::<f32>()?, &[0.0, 0.5, 1.0, 1.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reduce() -> LiteResult<()> {
+        let a = crate::host_tensor(layout!(u8; 2, 3), &[1u8, 5, 3, 200, 100, 201]);
+        let sum = a.sum(1)?;
+        assert_eq!(sum.dtype(), DataType::I64);
+        assert_eq!(sum.as_slice::<i64>()?, &[9, 501]);
+        assert_eq!(a.sum(0)?.as_slice::<i64>()?, &[201, 105, 204]);
+        assert_eq!(a.mean(1)?.as_slice::<f32>()?, &[3.0, 167.0]);
+        assert_eq!(a.max(0)?.as_slice::<u8>()?, &[200, 100, 201]);
+        assert_eq!(a.argmax(1)?.as_slice::<i32>()?, &[1, 2]);
+
+        let sub = a.slice(idx![.., 1..])?;
+        assert_eq!(sub.sum(1)?.as_slice::<i64>()?, &[8, 301]);
+
+        let big = (1i64 << 53) + 1;
+        let c = crate::host_tensor(layout!(i64; 3), &[big, 2, -1]);
+        assert_eq!(c.sum(0)?.as_slice::<i64>()?, &[big + 1]);
+        assert_eq!(c.max(0)?.as_slice::<i64>()?, &[big]);
+        let d = crate::host_tensor(layout!(i64; 2), &[big - 1, big]);
+        assert_eq!(d.argmax(0)?.as_slice::<i32>()?, &[1]);
+        assert_eq!(c.cast(DataType::U8)?.as_slice::<u8>()?, &[255, 2, 0]);
+
+        let b = crate::host_tensor(layout!(f32; 3), &[1.0f32, f32::NAN, 2.0]);
+        assert!(b.max(0)?.as_slice::<f32>()?[0].is_nan());
+        assert_eq!(b.argmax(0)?.as_slice::<i32>()?, &[1]);
+        assert_eq!(b.sum(0)?.shape(), &[1]);
+
+        assert!(matches!(a.sum(2), Err(LiteError::InvalidLayout(_))));
+        Ok(())
+    }
+}
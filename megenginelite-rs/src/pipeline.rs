@@ -0,0 +1,559 @@
+//! The pipeline of networks and functions over tensors
+
+use super::pool::fit_pool;
+use super::{utils, Layout, LiteError, LiteResult, Network, OwnedPoolSlot, Tensor, TensorPool};
+use async_channel::{bounded, Receiver, Sender};
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+enum Value {
+    Tensor(Tensor),
+    Slot(OwnedPoolSlot),
+}
+
+impl Deref for Value {
+    type Target = Tensor;
+    fn deref(&self) -> &Tensor {
+        match self {
+            Value::Tensor(tensor) => tensor,
+            Value::Slot(slot) => slot,
+        }
+    }
+}
+
+/// The tensors of the named edges which flow through a [`Pipeline`]
+#[derive(Default)]
+pub struct Frame {
+    edges: HashMap<String, Value>,
+}
+
+impl Frame {
+    /// An empty frame
+    pub fn new() -> Frame {
+        Frame::default()
+    }
+
+    /// Set the tensor of the edge `name`
+    pub fn insert(&mut self, name: impl Into<String>, tensor: Tensor) {
+        self.edges.insert(name.into(), Value::Tensor(tensor));
+    }
+
+    /// Get the tensor of the edge `name`
+    pub fn get(&self, name: &str) -> Option<&Tensor> {
+        self.edges.get(name).map(|v| &**v)
+    }
+
+    /// The names of the edges
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.edges.keys().map(|x| x.as_str())
+    }
+}
+
+/// The timing of a stage of the [`Pipeline`], see also [`Pipeline::stats`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageStats {
+    /// The name of the stage
+    pub name: String,
+    /// The number of processed frames, including the failed frames
+    pub frames: u64,
+    /// The number of failed frames
+    pub errors: u64,
+    /// The total time of processing
+    pub total_time: Duration,
+    /// The maximum time of processing a frame
+    pub max_time: Duration,
+}
+
+type StageFn = dyn FnMut(&[&Tensor]) -> LiteResult<Vec<Tensor>> + Send;
+
+/// The pools of the outputs of a network stage, which are created by the layout of the
+/// outputs, and closed when the pipeline is dropped
+type Pools = Arc<Mutex<Vec<Option<Arc<TensorPool>>>>>;
+
+enum Kind {
+    Network {
+        network: Network,
+        /// The io names of the network for the inputs and outputs of the stage
+        input_ios: Vec<String>,
+        output_ios: Vec<String>,
+        pools: Pools,
+    },
+    Function(Box<StageFn>),
+}
+
+struct Stage {
+    name: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    kind: Kind,
+}
+
+impl Stage {
+    fn run(&mut self, frame: &mut Frame, capacity: usize) -> LiteResult<()> {
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|edge| {
+                frame
+                    .get(edge)
+                    .ok_or_else(|| LiteError::MGELiteError(format!("the edge {} is missing", edge)))
+            })
+            .collect::<LiteResult<Vec<_>>>()?;
+
+        let outputs = match &mut self.kind {
+            Kind::Network {
+                network,
+                input_ios,
+                output_ios,
+                pools,
+            } => {
+                for (tensor, io) in inputs.iter().zip(input_ios.iter()) {
+                    let mut input = io_tensor(network, io)?;
+                    input.set_layout(Layout {
+                        shapes: tensor.shape(),
+                        data_type: tensor.dtype(),
+                    });
+                    input.copy_from(tensor)?;
+                }
+                network.exec_wait()?;
+
+                let mut outputs = vec![];
+                for (i, io) in output_ios.iter().enumerate() {
+                    let output = io_tensor(network, io)?;
+                    let pool = fit_pool(&mut pools.lock().unwrap()[i], &output, capacity)?;
+                    // the outputs are copied because they are overwritten by the next frame
+                    let mut slot = pool.get_owned_blocking()?;
+                    slot.copy_from(&output)?;
+                    outputs.push(Value::Slot(slot));
+                }
+                outputs
+            }
+            Kind::Function(f) => {
+                let outputs = f(&inputs)?;
+                if outputs.len() != self.outputs.len() {
+                    return Err(LiteError::MGELiteError(format!(
+                        "the function returns {} tensors, but {} outputs are expected",
+                        outputs.len(),
+                        self.outputs.len()
+                    )));
+                }
+                outputs.into_iter().map(Value::Tensor).collect()
+            }
+        };
+        for (edge, value) in self.outputs.iter().zip(outputs) {
+            frame.edges.insert(edge.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+fn io_tensor(network: &Network, name: &str) -> LiteResult<Tensor> {
+    network
+        .io_tensor(name)
+        .ok_or_else(|| LiteError::MGELiteError(format!("the network has no io named {}", name)))
+}
+
+/// The builder of [`Pipeline`]
+pub struct PipelineBuilder {
+    stages: Vec<Stage>,
+    capacity: usize,
+}
+
+impl PipelineBuilder {
+    /// Add a stage of `network`, `inputs` and `outputs` are the pairs of the edge name and
+    /// the io name of the network.
+    pub fn network(
+        mut self,
+        name: &str,
+        network: Network,
+        inputs: &[(&str, &str)],
+        outputs: &[(&str, &str)],
+    ) -> PipelineBuilder {
+        self.stages.push(Stage {
+            name: name.to_owned(),
+            inputs: inputs.iter().map(|x| x.0.to_owned()).collect(),
+            outputs: outputs.iter().map(|x| x.0.to_owned()).collect(),
+            kind: Kind::Network {
+                network,
+                input_ios: inputs.iter().map(|x| x.1.to_owned()).collect(),
+                output_ios: outputs.iter().map(|x| x.1.to_owned()).collect(),
+                pools: Arc::new(Mutex::new(vec![None; outputs.len()])),
+            },
+        });
+        self
+    }
+
+    /// Add a stage of the function `f`, which takes the tensors of the edges `inputs`
+    /// and returns the tensors of the edges `outputs`.
+    pub fn function<F>(mut self, name: &str, inputs: &[&str], outputs: &[&str], f: F) -> Self
+    where
+        F: FnMut(&[&Tensor]) -> LiteResult<Vec<Tensor>> + Send + 'static,
+    {
+        self.stages.push(Stage {
+            name: name.to_owned(),
+            inputs: inputs.iter().map(|&x| x.to_owned()).collect(),
+            outputs: outputs.iter().map(|&x| x.to_owned()).collect(),
+            kind: Kind::Function(Box::new(f)),
+        });
+        self
+    }
+
+    /// Set the capacity of the queues between the stages and the pools of the network
+    /// outputs, default 4
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Sort the stages by the edges, and start a thread for each stage
+    pub fn build(self) -> LiteResult<Pipeline> {
+        let stages = sort(self.stages)?;
+        let capacity = self.capacity;
+
+        let (input, mut r) = bounded::<LiteResult<Frame>>(capacity);
+        let mut stats = vec![];
+        let mut pools = vec![];
+        let mut workers = vec![];
+        for mut stage in stages {
+            if let Kind::Network { pools: p, .. } = &stage.kind {
+                pools.push(p.clone());
+            }
+            let (s, next) = bounded(capacity);
+            let stat = Arc::new(Mutex::new(StageStats {
+                name: stage.name.clone(),
+                frames: 0,
+                errors: 0,
+                total_time: Duration::ZERO,
+                max_time: Duration::ZERO,
+            }));
+            stats.push(stat.clone());
+            workers.push(std::thread::spawn(move || {
+                while let Some(Ok(item)) = utils::block_on(r.recv(), None) {
+                    let item: LiteResult<Frame> = item.and_then(|mut frame| {
+                        let start = Instant::now();
+                        // a panic only fails the frame, the stage keeps running
+                        let rst =
+                            catch_unwind(AssertUnwindSafe(|| stage.run(&mut frame, capacity)))
+                                .unwrap_or_else(|e| Err(panicked(e)));
+                        let elapsed = start.elapsed();
+                        let mut stat = stat.lock().unwrap();
+                        stat.frames += 1;
+                        stat.errors += rst.is_err() as u64;
+                        stat.total_time += elapsed;
+                        stat.max_time = stat.max_time.max(elapsed);
+                        match rst {
+                            Ok(()) => Ok(frame),
+                            Err(e) => Err(LiteError::StageFailed {
+                                stage: stage.name.clone(),
+                                error: Box::new(e),
+                            }),
+                        }
+                    });
+                    if utils::block_on(s.send(item), None).unwrap().is_err() {
+                        break;
+                    }
+                }
+            }));
+            r = next;
+        }
+        Ok(Pipeline {
+            input,
+            output: r,
+            stats,
+            pools,
+            workers,
+        })
+    }
+}
+
+fn panicked(payload: Box<dyn std::any::Any + Send>) -> LiteError {
+    let msg = match payload.downcast_ref::<&str>() {
+        Some(msg) => msg.to_string(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default(),
+    };
+    LiteError::MGELiteError(format!("the stage panicked: {}", msg))
+}
+
+/// Sort the stages so that every stage is after the producers of its inputs
+fn sort(mut stages: Vec<Stage>) -> LiteResult<Vec<Stage>> {
+    let mut producers = HashMap::new();
+    for (i, stage) in stages.iter().enumerate() {
+        for edge in &stage.outputs {
+            if producers.insert(edge.clone(), i).is_some() {
+                return Err(LiteError::MGELiteError(format!(
+                    "the edge {} is produced by more than one stage",
+                    edge
+                )));
+            }
+        }
+    }
+    let deps: Vec<Vec<usize>> = stages
+        .iter()
+        .map(|stage| {
+            stage
+                .inputs
+                .iter()
+                .filter_map(|edge| producers.get(edge).copied())
+                .collect()
+        })
+        .collect();
+
+    let mut order = vec![];
+    let mut done = vec![false; stages.len()];
+    while order.len() < stages.len() {
+        let next = (0..stages.len())
+            .find(|&i| !done[i] && deps[i].iter().all(|&d| done[d]))
+            .ok_or_else(|| LiteError::MGELiteError("the stages have a cycle".into()))?;
+        done[next] = true;
+        order.push(next);
+    }
+
+    let mut stages: Vec<_> = stages.drain(..).map(Some).collect();
+    Ok(order
+        .into_iter()
+        .map(|i| stages[i].take().unwrap())
+        .collect())
+}
+
+/// A pipeline whose stages are networks or functions over tensors, connected by named
+/// edges
+///
+/// The stages are executed in the order of the edges, each stage runs in its own thread,
+/// so the stages process different frames concurrently. The frames are passed through
+/// bounded queues, and the network outputs are copied into the host memory of
+/// [`TensorPool`]s, so the pipeline is blocked when the downstream is slow. The edges
+/// which are not produced by any stage are the inputs of the pipeline.
+///
+/// A failed frame skips the remaining stages and is received as
+/// [`LiteError::StageFailed`], the later frames are not affected.
+///
+/// The frames are received in the order they are sent, receive them concurrently with
+/// sending, otherwise the sender is blocked when the queues are full.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # #[tokio::main]
+/// # async fn main() -> LiteResult<()> {
+/// let detector = Network::builder().build("detector_path")?;
+/// let classifier = Network::builder().build("classifier_path")?;
+/// let pipeline = Pipeline::builder()
+///     .network("detector", detector, &[("image", "data")], &[("boxes", "output")])
+///     .function("cropper", &["image", "boxes"], &["crops"], |inputs| {
+///         // crop the image by the boxes ...
+///         Ok(vec![inputs[0].try_clone()?])
+///     })
+///     .network("classifier", classifier, &[("crops", "data")], &[("scores", "output")])
+///     .build()?;
+///
+/// let mut frame = Frame::new();
+/// frame.insert("image", Tensor::host()?);
+/// pipeline.send(frame).await?;
+/// let frame = pipeline.recv().await.unwrap()?;
+/// let scores = frame.get("scores").unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub struct Pipeline {
+    input: Sender<LiteResult<Frame>>,
+    output: Receiver<LiteResult<Frame>>,
+    stats: Vec<Arc<Mutex<StageStats>>>,
+    pools: Vec<Pools>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Pipeline {
+    /// Get a builder to build pipeline
+    pub fn builder() -> PipelineBuilder {
+        PipelineBuilder {
+            stages: vec![],
+            capacity: 4,
+        }
+    }
+
+    /// Send a frame, will wait if the queue is full
+    ///
+    /// Return an error if the pipeline is closed, see also [`Pipeline::close`]
+    pub async fn send(&self, frame: Frame) -> LiteResult<()> {
+        self.input
+            .send(Ok(frame))
+            .await
+            .map_err(|_| LiteError::PipelineClosed)
+    }
+
+    /// Send a frame, will block the current thread if the queue is full, see also
+    /// [`Pipeline::send`]
+    pub fn send_blocking(&self, frame: Frame) -> LiteResult<()> {
+        utils::block_on(self.send(frame), None).unwrap()
+    }
+
+    /// Receive a processed frame, return `None` if the pipeline is closed and all frames
+    /// are received
+    pub async fn recv(&self) -> Option<LiteResult<Frame>> {
+        self.output.recv().await.ok()
+    }
+
+    /// Receive a processed frame, will block the current thread, see also
+    /// [`Pipeline::recv`]
+    pub fn recv_blocking(&self) -> Option<LiteResult<Frame>> {
+        utils::block_on(self.recv(), None).unwrap()
+    }
+
+    /// Close the pipeline, the sent frames are still processed
+    pub fn close(&self) {
+        self.input.close();
+    }
+
+    /// Get a snapshot of the timing of the stages, in the order of execution
+    pub fn stats(&self) -> Vec<StageStats> {
+        self.stats
+            .iter()
+            .map(|stat| stat.lock().unwrap().clone())
+            .collect()
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.input.close();
+        self.output.close();
+        // the stages waiting for the slots held by the unreceived frames are woken up
+        for pools in &self.pools {
+            let pools = pools.lock().unwrap_or_else(|e| e.into_inner());
+            for pool in pools.iter().flatten() {
+                pool.close();
+            }
+        }
+        for worker in self.workers.drain(..) {
+            worker.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    fn scalar(x: f32) -> LiteResult<Tensor> {
+        let mut tensor = Tensor::host()?;
+        tensor.set_layout(layout!(f32; 1));
+        tensor.as_slice_mut::<f32>()?[0] = x;
+        Ok(tensor)
+    }
+
+    #[test]
+    fn test_pipeline() -> LiteResult<()> {
+        let pipeline = Pipeline::builder()
+            // added in the reverse order
+            .function("sum", &["a", "b"], &["c"], |x| Ok(vec![x[0].add(x[1])?]))
+            .function("double", &["x"], &["a", "b"], |x| {
+                if x[0].as_slice::<f32>()?[0] < 0.0 {
+                    return Err(LiteError::InvalidLayout("negative".into()));
+                }
+                Ok(vec![x[0].try_clone()?, x[0].try_clone()?])
+            })
+            .capacity(2)
+            .build()?;
+
+        for x in [1.0, -1.0, 2.0] {
+            let mut frame = Frame::new();
+            frame.insert("x", scalar(x)?);
+            pipeline.send_blocking(frame)?;
+        }
+        pipeline.close();
+
+        let frame = pipeline.recv_blocking().unwrap()?;
+        assert_eq!(frame.get("c").unwrap().as_slice::<f32>()?, &[2.0]);
+        assert!(matches!(
+            pipeline.recv_blocking().unwrap(),
+            Err(LiteError::StageFailed { stage, .. }) if stage == "double"
+        ));
+        let frame = pipeline.recv_blocking().unwrap()?;
+        assert_eq!(frame.get("c").unwrap().as_slice::<f32>()?, &[4.0]);
+        assert!(pipeline.recv_blocking().is_none());
+
+        let stats = pipeline.stats();
+        assert_eq!(stats[0].name, "double");
+        assert_eq!((stats[0].frames, stats[0].errors), (3, 1));
+        assert_eq!((stats[1].frames, stats[1].errors), (2, 0));
+
+        let pipeline = Pipeline::builder()
+            .function("panic", &["x"], &["y"], |x| {
+                if x[0].as_slice::<f32>()?[0] < 0.0 {
+                    panic!("negative");
+                }
+                Ok(vec![x[0].try_clone()?])
+            })
+            .build()?;
+        for x in [-1.0, 1.0] {
+            let mut frame = Frame::new();
+            frame.insert("x", scalar(x)?);
+            pipeline.send_blocking(frame)?;
+        }
+        assert!(matches!(
+            pipeline.recv_blocking().unwrap(),
+            Err(LiteError::StageFailed { stage, .. }) if stage == "panic"
+        ));
+        let frame = pipeline.recv_blocking().unwrap()?;
+        assert_eq!(frame.get("y").unwrap().as_slice::<f32>()?, &[1.0]);
+        assert_eq!(pipeline.stats()[0].errors, 1);
+        drop(pipeline);
+
+        assert!(Pipeline::builder()
+            .function("a", &["x"], &["y"], |_| Ok(vec![]))
+            .function("b", &["y"], &["x"], |_| Ok(vec![]))
+            .build()
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pipeline_network() -> LiteResult<()> {
+        let mut network = Network::builder().build(crate::model_path())?;
+        let output = network.output_names()[0].to_owned();
+        let image = || -> LiteResult<Frame> {
+            let mut image = Tensor::host()?;
+            image.set_layout(layout!(f32; 1, 3, 224, 224));
+            image.as_slice_mut::<f32>()?.fill(1.0);
+            let mut frame = Frame::new();
+            frame.insert("image", image);
+            Ok(frame)
+        };
+        network
+            .io_tensor("data")
+            .unwrap()
+            .copy_from(image()?.get("image").unwrap())?;
+        network.exec_wait()?;
+        let expected = network.io_tensor(&output).unwrap().try_clone()?;
+
+        let pipeline = Pipeline::builder()
+            .network("net", network, &[("image", "data")], &[("scores", &output)])
+            .capacity(1)
+            .build()?;
+        pipeline.send_blocking(image()?)?;
+        let frame = pipeline.recv_blocking().unwrap()?;
+        let scores = frame.get("scores").unwrap();
+        assert_eq!(scores.shape(), &[1, 1000]);
+        assert!(scores.allclose(&expected, 1e-5, 1e-5)?);
+
+        // the stage is blocked on the slot held by `frame`, dropping must not hang
+        pipeline.send_blocking(image()?)?;
+        let waits = || {
+            let pools = pipeline.pools[0].lock().unwrap();
+            pools[0].as_ref().map_or(0, |pool| pool.stats().waits)
+        };
+        while waits() == 0 {
+            std::thread::yield_now();
+        }
+        drop(pipeline);
+        assert_eq!(frame.get("scores").unwrap().shape(), &[1, 1000]);
+        Ok(())
+    }
+}
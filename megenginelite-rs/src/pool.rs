@@ -1,6 +1,11 @@
 use super::ffi::LiteDeviceType;
 use super::tensor::*;
-use super::{idx, LiteResult};
+use super::{idx, utils, DataType, LiteError, LiteResult};
+use std::marker::PhantomData;
+use std::ops::DerefMut;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// A tensor pool to reuse memory
 ///
@@ -15,8 +20,8 @@ use super::{idx, LiteResult};
 /// })?;
 /// let free_n = pool.free_n();
 /// {
-///     let idx = pool.get().await;
-///     let tensor = pool.at(&idx);
+///     let mut tensor = pool.get().await?;
+///     tensor.fill_zero();
 ///     assert_eq!(free_n, pool.free_n() + 1);
 /// }
 /// assert_eq!(free_n, pool.free_n());
@@ -66,6 +71,25 @@ impl TensorPool {
         mem.set_layout(layout);
         Ok(Self::new(mem, freelist))
     }
+    /// Create a pool with host memory aligned to `align` bytes, see also
+    /// [`Tensor::host_aligned()`]
+    ///
+    /// Every tensor in the pool is aligned, so the size of a tensor in bytes must be a
+    /// multiple of `align`.
+    pub fn host_aligned(layout: Layout, align: usize) -> LiteResult<Self> {
+        let n = layout.shapes[0];
+        let block =
+            layout.shapes[1..].iter().product::<usize>() * DataType::width(layout.data_type);
+        let mem = Tensor::host_aligned(layout, align)?;
+        // `align` is checked to be a power of two
+        if n > 1 && block & (align - 1) != 0 {
+            return Err(LiteError::InvalidLayout(format!(
+                "the tensor size {} of pool is not a multiple of alignment {}",
+                block, align
+            )));
+        }
+        Ok(Self::new(mem, FreeList::new(n)))
+    }
     /// Get the data pointer of the inner tensor
     pub fn as_ptr<T>(&self) -> *const T {
         self.phead as _
@@ -74,13 +98,208 @@ impl TensorPool {
     pub fn as_tensor(&self) -> &Tensor {
         &self.mem
     }
-    /// Request an index, will block if the pool is empty
-    pub async fn get(&self) -> Idx {
-        self.freelist.pop().await
+    /// Request a slot, will wait if the pool is empty
+    ///
+    /// Return an error if the pool is closed, see also [`TensorPool::close`]
+    pub async fn get(&self) -> LiteResult<PoolSlot<'_>> {
+        self.slot(self.freelist.pop().await?)
+    }
+    /// Request a slot, will block the current thread if the pool is empty, see also
+    /// [`TensorPool::get`]
+    pub fn get_blocking(&self) -> LiteResult<PoolSlot<'_>> {
+        self.slot(utils::block_on(self.freelist.pop(), None).unwrap()?)
+    }
+    /// Request a slot, return `None` if the pool is empty or closed
+    pub fn try_get(&self) -> Option<PoolSlot<'_>> {
+        self.slot(self.freelist.try_pop()?).ok()
+    }
+    /// Request a slot, will block the current thread for at most `timeout` if the pool
+    /// is empty, return `Ok(None)` on timeout, see also [`TensorPool::get`]
+    pub fn get_timeout(&self, timeout: Duration) -> LiteResult<Option<PoolSlot<'_>>> {
+        match utils::block_on(self.freelist.pop(), Some(Instant::now() + timeout)) {
+            Some(idx) => Ok(Some(self.slot(idx?)?)),
+            None => Ok(None),
+        }
+    }
+    /// Request a slot which holds the pool, so it can be sent across tasks, see also
+    /// [`TensorPool::get`]
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # use std::sync::Arc;
+    /// # #[tokio::main]
+    /// # async fn main() -> LiteResult<()> {
+    /// let pool = Arc::new(TensorPool::host(layout!(u8; 4, 1080, 1920, 3))?);
+    /// let slot = pool.clone().get_owned().await?;
+    /// tokio::spawn(async move {
+    ///     let frame = slot.as_slice::<u8>();
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_owned(self: Arc<Self>) -> LiteResult<OwnedPoolSlot> {
+        let idx = self.freelist.pop().await?;
+        OwnedPoolSlot::new(self, idx)
+    }
+    /// Request a slot which holds the pool, will block the current thread if the pool is
+    /// empty, see also [`TensorPool::get_owned`]
+    pub fn get_owned_blocking(self: Arc<Self>) -> LiteResult<OwnedPoolSlot> {
+        let idx = utils::block_on(self.freelist.pop(), None).unwrap()?;
+        OwnedPoolSlot::new(self, idx)
+    }
+    /// Request a slot which holds the pool, return `None` if the pool is empty or closed,
+    /// see also [`TensorPool::get_owned`]
+    pub fn try_get_owned(self: Arc<Self>) -> Option<OwnedPoolSlot> {
+        let idx = self.freelist.try_pop()?;
+        OwnedPoolSlot::new(self, idx).ok()
+    }
+    /// Get a snapshot of the statistics
+    pub fn stats(&self) -> PoolStats {
+        self.freelist.stats()
+    }
+    /// Set the label `pool` of the metrics reported by the pool, the metrics are
+    /// `megenginelite_pool_acquisitions_total`, `megenginelite_pool_wait_seconds` and
+    /// `megenginelite_pool_in_use`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # fn main() -> LiteResult<()> {
+    /// let pool = TensorPool::host(layout!(u8; 4, 1080, 1920, 3))?.with_name("frames");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn with_name(mut self, name: impl Into<metrics::SharedString>) -> Self {
+        self.freelist.set_name(name.into());
+        self
+    }
+    /// Close the pool, all waiting and later requests return an error
+    pub fn close(&self) {
+        self.freelist.close();
+    }
+    /// Whether the pool is closed
+    pub fn is_closed(&self) -> bool {
+        self.freelist.is_closed()
     }
     /// Get the tensor at `idx`
-    pub fn at(&self, idx: &Idx) -> Tensor {
-        self.mem.slice(idx![idx.get()])
+    ///
+    /// The tensor is not tied to `idx`, so it still points to the memory after the slot
+    /// is given back and checked out again.
+    #[deprecated(note = "use the tensor of `get` or `get_owned`, which holds the slot")]
+    pub fn at(&self, idx: &Idx) -> LiteResult<Tensor> {
+        self.tensor_at(idx.get())
+    }
+    fn tensor_at(&self, i: usize) -> LiteResult<Tensor> {
+        let mut tensor = self.mem.slice(idx![i])?;
+        // the indexed dim is kept by `slice`
+        let shape: Vec<_> = self.mem.shape()[1..].iter().map(|&x| x as i32).collect();
+        if !shape.is_empty() {
+            tensor.reshape(&shape)?;
+        }
+        Ok(tensor)
+    }
+    fn slot(&self, idx: Idx) -> LiteResult<PoolSlot<'_>> {
+        let (tensor, idx) = self.checkout(idx)?;
+        Ok(PoolSlot {
+            tensor,
+            idx,
+            phantom: PhantomData,
+        })
+    }
+    /// The tensor at `idx` which holds `idx`, so do its views
+    fn checkout(&self, idx: Idx) -> LiteResult<(Tensor, Arc<Idx>)> {
+        let idx = Arc::new(idx);
+        let mut tensor = self.tensor_at(idx.get())?;
+        tensor.hold(idx.clone());
+        Ok((tensor, idx))
+    }
+}
+
+/// Get the pool of `capacity` tensors with the layout of `tensor`, which is recreated if
+/// the layout of `tensor` is changed
+pub(crate) fn fit_pool(
+    pool: &mut Option<Arc<TensorPool>>,
+    tensor: &Tensor,
+    capacity: usize,
+) -> LiteResult<Arc<TensorPool>> {
+    let matched = matches!(pool, Some(pool) if {
+        let mem = pool.as_tensor();
+        mem.dtype() == tensor.dtype() && mem.shape()[1..] == *tensor.shape()
+    });
+    if !matched {
+        let mut shape = vec![capacity];
+        shape.extend_from_slice(tensor.shape());
+        *pool = Some(Arc::new(TensorPool::host(Layout {
+            shapes: &shape,
+            data_type: tensor.dtype(),
+        })?));
+    }
+    Ok(pool.clone().unwrap())
+}
+
+/// A checked-out tensor of the [`TensorPool`], which derefs to the tensor, the index is
+/// given back after the tensor and its views are dropped.
+pub struct PoolSlot<'a> {
+    tensor: Tensor,
+    idx: Arc<Idx>,
+    phantom: PhantomData<&'a TensorPool>,
+}
+
+impl<'a> PoolSlot<'a> {
+    /// Get the index
+    pub fn idx(&self) -> &Idx {
+        &self.idx
+    }
+}
+
+impl<'a> Deref for PoolSlot<'a> {
+    type Target = Tensor;
+    fn deref(&self) -> &Tensor {
+        &self.tensor
+    }
+}
+
+impl<'a> DerefMut for PoolSlot<'a> {
+    fn deref_mut(&mut self) -> &mut Tensor {
+        &mut self.tensor
+    }
+}
+
+/// A checked-out tensor of the [`TensorPool`] which holds the pool, see also
+/// [`TensorPool::get_owned`] and [`PoolSlot`]
+pub struct OwnedPoolSlot {
+    tensor: Tensor,
+    idx: Arc<Idx>,
+    _pool: Arc<TensorPool>,
+}
+
+impl OwnedPoolSlot {
+    fn new(pool: Arc<TensorPool>, idx: Idx) -> LiteResult<Self> {
+        let (tensor, idx) = pool.checkout(idx)?;
+        Ok(OwnedPoolSlot {
+            tensor,
+            idx,
+            _pool: pool,
+        })
+    }
+    /// Get the index
+    pub fn idx(&self) -> &Idx {
+        &self.idx
+    }
+}
+
+impl Deref for OwnedPoolSlot {
+    type Target = Tensor;
+    fn deref(&self) -> &Tensor {
+        &self.tensor
+    }
+}
+
+impl DerefMut for OwnedPoolSlot {
+    fn deref_mut(&mut self) -> &mut Tensor {
+        &mut self.tensor
     }
 }
 
@@ -91,6 +310,7 @@ use std::{fmt::Display, ops::Deref};
 pub struct Idx {
     id: usize,
     s: Sender<usize>,
+    stats: Arc<Stats>,
 }
 
 impl Idx {
@@ -118,33 +338,257 @@ impl Drop for Idx {
     fn drop(&mut self) {
         // never block because the number of datas is equal to the capacity of queue
         self.s.try_send(self.id).ok();
+        self.stats
+            .release(self.s.capacity().unwrap() - self.s.len());
+    }
+}
+
+/// A snapshot of the statistics of [`TensorPool`] or [`NetworkPool`](crate::NetworkPool),
+/// see also [`TensorPool::stats`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// The number of tensors in the pool
+    pub capacity: usize,
+    /// The number of tensors checked out
+    pub in_use: usize,
+    /// The maximum number of tensors checked out at the same time
+    pub high_water: usize,
+    /// The total number of acquisitions
+    pub acquisitions: u64,
+    /// The number of acquisitions which found the pool empty and had to wait
+    pub waits: u64,
+    /// The total time waiting for acquisitions
+    pub total_wait: Duration,
+    /// The maximum time waiting for an acquisition
+    pub max_wait: Duration,
+}
+
+#[derive(Default)]
+struct Stats {
+    high_water: AtomicUsize,
+    acquisitions: AtomicU64,
+    waits: AtomicU64,
+    /// in nanoseconds
+    total_wait: AtomicU64,
+    /// in nanoseconds
+    max_wait: AtomicU64,
+    /// The label `pool` of metrics
+    #[cfg(feature = "metrics")]
+    name: metrics::SharedString,
+}
+
+impl Stats {
+    fn acquire(&self, in_use: usize, wait: Duration) {
+        let ns = wait.as_nanos() as u64;
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        self.total_wait.fetch_add(ns, Ordering::Relaxed);
+        self.max_wait.fetch_max(ns, Ordering::Relaxed);
+        self.high_water.fetch_max(in_use, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        {
+            let name = &self.name;
+            metrics::counter!("megenginelite_pool_acquisitions_total", 1, "pool" => name.clone());
+            let secs = wait.as_secs_f64();
+            metrics::histogram!("megenginelite_pool_wait_seconds", secs, "pool" => name.clone());
+            metrics::gauge!("megenginelite_pool_in_use", in_use as f64, "pool" => name.clone());
+        }
+    }
+
+    #[allow(unused_variables)]
+    fn release(&self, in_use: usize) {
+        #[cfg(feature = "metrics")]
+        metrics::gauge!("megenginelite_pool_in_use", in_use as f64, "pool" => self.name.clone());
     }
 }
 
-struct FreeList {
+pub(crate) struct FreeList {
     s: Sender<usize>,
     r: Receiver<usize>,
+    n: usize,
+    stats: Arc<Stats>,
 }
 
 impl FreeList {
-    fn new(n: usize) -> FreeList {
+    pub(crate) fn new(n: usize) -> FreeList {
         let (s, r) = bounded(n);
         for i in 0..n {
             s.try_send(i).ok();
         }
         assert_eq!(s.len(), n);
-        FreeList { s, r }
+        FreeList {
+            s,
+            r,
+            n,
+            stats: Default::default(),
+        }
     }
 
     #[inline]
-    async fn pop(&self) -> Idx {
+    fn take(&self, id: usize, start: Instant) -> Idx {
+        self.stats.acquire(self.n - self.r.len(), start.elapsed());
         Idx {
-            id: self.r.recv().await.unwrap(),
+            id,
             s: self.s.clone(),
+            stats: self.stats.clone(),
+        }
+    }
+    #[cfg(feature = "metrics")]
+    fn set_name(&mut self, name: metrics::SharedString) {
+        match Arc::get_mut(&mut self.stats) {
+            Some(stats) => stats.name = name,
+            // some views of the slots are still alive, they keep the former label
+            None => {
+                let load = |x: &AtomicU64| AtomicU64::new(x.load(Ordering::Relaxed));
+                let old = &self.stats;
+                self.stats = Arc::new(Stats {
+                    high_water: AtomicUsize::new(old.high_water.load(Ordering::Relaxed)),
+                    acquisitions: load(&old.acquisitions),
+                    waits: load(&old.waits),
+                    total_wait: load(&old.total_wait),
+                    max_wait: load(&old.max_wait),
+                    name,
+                });
+            }
         }
     }
     #[inline]
-    fn len(&self) -> usize {
+    pub(crate) async fn pop(&self) -> LiteResult<Idx> {
+        let start = Instant::now();
+        if self.r.is_closed() {
+            return Err(LiteError::PoolClosed);
+        }
+        let id = match self.r.try_recv() {
+            Ok(id) => id,
+            Err(_) => {
+                self.stats.waits.fetch_add(1, Ordering::Relaxed);
+                self.r.recv().await.map_err(|_| LiteError::PoolClosed)?
+            }
+        };
+        Ok(self.take(id, start))
+    }
+    #[inline]
+    pub(crate) fn try_pop(&self) -> Option<Idx> {
+        let start = Instant::now();
+        if self.r.is_closed() {
+            return None;
+        }
+        let id = self.r.try_recv().ok()?;
+        Some(self.take(id, start))
+    }
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
         self.s.len()
     }
+    pub(crate) fn close(&self) {
+        self.s.close();
+    }
+    pub(crate) fn is_closed(&self) -> bool {
+        self.s.is_closed()
+    }
+    pub(crate) fn stats(&self) -> PoolStats {
+        let stats = &self.stats;
+        PoolStats {
+            capacity: self.n,
+            in_use: self.n - self.len(),
+            high_water: stats.high_water.load(Ordering::Relaxed),
+            acquisitions: stats.acquisitions.load(Ordering::Relaxed),
+            waits: stats.waits.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(stats.total_wait.load(Ordering::Relaxed)),
+            max_wait: Duration::from_nanos(stats.max_wait.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_get() -> LiteResult<()> {
+        let pool = TensorPool::host(layout!(u8; 2, 10))?;
+        let a = pool.get_blocking()?;
+        let b = pool.try_get().unwrap();
+        assert_ne!(a.idx().get(), b.idx().get());
+        assert_eq!(a.shape(), &[10]);
+        assert!(pool.try_get().is_none());
+        assert!(pool.get_timeout(Duration::from_millis(10))?.is_none());
+        drop(a);
+        assert!(pool.get_timeout(Duration::from_millis(10))?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> LiteResult<()> {
+        let pool = TensorPool::host(layout!(u8; 3, 10))?;
+        let a = pool.get_blocking()?;
+        let b = pool.get_blocking()?;
+        drop(a);
+        let c = pool.try_get().unwrap();
+        let stats = pool.stats();
+        assert_eq!(stats.capacity, 3);
+        assert_eq!(stats.in_use, 2);
+        assert_eq!(stats.high_water, 2);
+        assert_eq!(stats.acquisitions, 3);
+        drop((b, c));
+        assert_eq!(pool.stats().in_use, 0);
+
+        let pool = std::sync::Arc::new(TensorPool::host(layout!(u8; 1, 10))?);
+        let slot = pool.get_blocking()?;
+        assert_eq!(pool.stats().waits, 0);
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || pool.get_blocking().map(|_| ()))
+        };
+        while pool.stats().waits == 0 {
+            std::thread::yield_now();
+        }
+        drop(slot);
+        waiter.join().unwrap()?;
+        let stats = pool.stats();
+        assert_eq!(stats.acquisitions, 2);
+        assert_eq!(stats.waits, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_slot() -> LiteResult<()> {
+        let pool = std::sync::Arc::new(TensorPool::host(layout!(u8; 1, 10))?);
+        let mut slot = pool.get_blocking()?;
+        slot.fill_zero();
+        let view = slot.slice(idx![0..5])?;
+        drop(slot);
+        // the index is given back after the views are dropped
+        assert_eq!(pool.free_n(), 0);
+        drop(view);
+        assert_eq!(pool.free_n(), 1);
+
+        let slot = pool.clone().get_owned_blocking()?;
+        assert_eq!(pool.free_n(), 0);
+        assert!(pool.clone().try_get_owned().is_none());
+        let slot = std::thread::spawn(move || slot.as_slice::<u8>().map(|x| x.len()))
+            .join()
+            .unwrap()?;
+        assert_eq!(slot, 10);
+        assert_eq!(pool.free_n(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_close() -> LiteResult<()> {
+        let pool = std::sync::Arc::new(TensorPool::host(layout!(u8; 1, 10))?);
+        let slot = pool.get_blocking()?;
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || pool.get_blocking().map(|_| ()))
+        };
+        std::thread::sleep(Duration::from_millis(10));
+        pool.close();
+        assert!(matches!(waiter.join().unwrap(), Err(LiteError::PoolClosed)));
+        drop(slot);
+        assert!(pool.is_closed());
+        assert!(pool.try_get().is_none());
+        assert!(matches!(pool.get_blocking(), Err(LiteError::PoolClosed)));
+        Ok(())
+    }
 }
@@ -0,0 +1,260 @@
+//! The safetensors format support
+
+use super::*;
+use crate::ffi::*;
+use ::safetensors::tensor::{Dtype, Metadata, SafeTensors, TensorInfo, View};
+use std::borrow::Cow;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+impl From<::safetensors::SafeTensorError> for LiteError {
+    fn from(e: ::safetensors::SafeTensorError) -> Self {
+        match e {
+            ::safetensors::SafeTensorError::IoError(e) => LiteError::IOError(e),
+            e => LiteError::InvalidFormat(e.to_string()),
+        }
+    }
+}
+
+fn dtype(ty: LiteDataType) -> LiteResult<Dtype> {
+    Ok(match ty {
+        DataType::F32 => Dtype::F32,
+        DataType::F16 => Dtype::F16,
+        DataType::I32 => Dtype::I32,
+        DataType::I16 => Dtype::I16,
+        DataType::I8 => Dtype::I8,
+        DataType::U32 => Dtype::U32,
+        DataType::U16 => Dtype::U16,
+        DataType::U8 => Dtype::U8,
+        DataType::I64 => Dtype::I64,
+        _ => {
+            return Err(LiteError::InvalidFormat(format!(
+                "data type {} is not supported by safetensors",
+                ty
+            )))
+        }
+    })
+}
+
+fn data_type(dtype: Dtype) -> LiteResult<LiteDataType> {
+    Ok(match dtype {
+        Dtype::F32 => DataType::F32,
+        Dtype::F16 => DataType::F16,
+        Dtype::I32 => DataType::I32,
+        Dtype::I16 => DataType::I16,
+        Dtype::I8 => DataType::I8,
+        Dtype::U32 => DataType::U32,
+        Dtype::U16 => DataType::U16,
+        Dtype::U8 => DataType::U8,
+        Dtype::I64 => DataType::I64,
+        _ => {
+            return Err(LiteError::InvalidFormat(format!(
+                "safetensors dtype {:?} is not supported",
+                dtype
+            )))
+        }
+    })
+}
+
+/// A safetensors file mapped in memory
+///
+/// The host tensors read from it borrow the mapped memory without copy, and keep the
+/// mapping alive. The mapping is copy-on-write, so writing to these tensors never
+/// changes the file.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # fn main() -> LiteResult<()> {
+/// let network = Network::builder().build("model_path")?;
+/// let mut input = network.io_tensor("data").unwrap();
+///
+/// let file = SafeTensorsFile::open("inputs.safetensors")?;
+/// input.copy_from(&file.tensor("data")?)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SafeTensorsFile {
+    mmap: Arc<memmap2::MmapMut>,
+    phead: *mut u8,
+    metadata: Metadata,
+}
+
+unsafe impl Send for SafeTensorsFile {}
+unsafe impl Sync for SafeTensorsFile {}
+
+impl SafeTensorsFile {
+    /// Map a safetensors file into memory, and parse the header
+    pub fn open(path: impl AsRef<Path>) -> LiteResult<Self> {
+        let file = File::open(path)?;
+        let mut mmap = unsafe { memmap2::MmapOptions::new().map_copy(&file)? };
+        let (n, metadata) = SafeTensors::read_metadata(&mmap)?;
+        let phead = unsafe { mmap.as_mut_ptr().add(n + 8) };
+        Ok(SafeTensorsFile {
+            mmap: Arc::new(mmap),
+            phead,
+            metadata,
+        })
+    }
+
+    /// The names of tensors in the file
+    pub fn names(&self) -> Vec<String> {
+        self.metadata.tensors().into_keys().collect()
+    }
+
+    /// Get the tensor `name` as a host tensor which borrows the mapped memory, the data
+    /// is copied only if it is not aligned to the data type.
+    pub fn tensor(&self, name: &str) -> LiteResult<Tensor> {
+        let tensors = self.metadata.tensors();
+        let info = tensors.get(name).ok_or_else(|| {
+            LiteError::InvalidFormat(format!("safetensors has no tensor {}", name))
+        })?;
+        self.load(info)
+    }
+
+    /// Get all tensors in the file, see also [`SafeTensorsFile::tensor`]
+    pub fn tensors(&self) -> LiteResult<Vec<(String, Tensor)>> {
+        self.metadata
+            .tensors()
+            .into_iter()
+            .map(|(name, info)| Ok((name, self.load(info)?)))
+            .collect()
+    }
+
+    fn load(&self, info: &TensorInfo) -> LiteResult<Tensor> {
+        if info.shape.len() > LAYOUT_MAX_DIM as usize {
+            return Err(LiteError::InvalidFormat(format!(
+                "safetensors tensor has {} dims, but the maximum dim is {}",
+                info.shape.len(),
+                LAYOUT_MAX_DIM
+            )));
+        }
+        let data_type = data_type(info.dtype)?;
+        // a scalar is loaded as a tensor of shape (1,)
+        let shape = if info.shape.is_empty() {
+            vec![1]
+        } else {
+            info.shape.clone()
+        };
+        let mut tensor = Tensor::host()?;
+        tensor.set_layout(Layout {
+            shapes: &shape,
+            data_type,
+        });
+
+        let (begin, end) = info.data_offsets;
+        if end == begin {
+            return Ok(tensor);
+        }
+        let p = unsafe { self.phead.add(begin) };
+        if p.align_offset(DataType::width(data_type)) == 0 {
+            unsafe { tensor.reset_memory(p as *mut _, end - begin, self.mmap.clone())? };
+        } else {
+            let data = unsafe { std::slice::from_raw_parts(p, end - begin) };
+            tensor.as_slice_mut::<u8>()?.copy_from_slice(data);
+        }
+        Ok(tensor)
+    }
+}
+
+struct HostView {
+    tensor: Tensor,
+    dtype: Dtype,
+}
+
+impl View for &HostView {
+    fn dtype(&self) -> Dtype {
+        self.dtype
+    }
+
+    fn shape(&self) -> &[usize] {
+        self.tensor.shape()
+    }
+
+    fn data(&self) -> Cow<[u8]> {
+        Cow::Borrowed(self.tensor.as_slice().unwrap())
+    }
+
+    fn data_len(&self) -> usize {
+        self.tensor.nbytes()
+    }
+}
+
+/// Save named tensors to a safetensors file, device tensors are copied to host first.
+///
+/// # Example
+/// ```no_run
+/// # use megenginelite_rs::*;
+/// # fn main() -> LiteResult<()> {
+/// let mut network = Network::builder().build("model_path")?;
+/// network.exec_wait()?;
+/// let input = network.io_tensor("data").unwrap();
+/// let output = network.io_tensor("output").unwrap();
+/// save_safetensors("run.safetensors", &[("data", &input), ("output", &output)])?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn save_safetensors(path: impl AsRef<Path>, tensors: &[(&str, &Tensor)]) -> LiteResult<()> {
+    let views = tensors
+        .iter()
+        .map(|(name, tensor)| {
+            let host = match tensor.packed_host()? {
+                Some(host) => host,
+                None => tensor.view()?,
+            };
+            Ok((
+                *name,
+                HostView {
+                    dtype: dtype(host.dtype())?,
+                    tensor: host,
+                },
+            ))
+        })
+        .collect::<LiteResult<Vec<_>>>()?;
+    ::safetensors::tensor::serialize_to_file(
+        views.iter().map(|(name, view)| (*name, view)),
+        &None,
+        path.as_ref(),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_safetensors() -> LiteResult<()> {
+        let mut a = Tensor::host()?;
+        a.set_layout(layout!(f32; 2, 2));
+        a.as_slice_mut::<f32>()?
+            .copy_from_slice(&[1.0, 2.0, 3.0, 4.0]);
+        let mut b = Tensor::host()?;
+        b.set_layout(layout!(i64; 3));
+        b.as_slice_mut::<i64>()?.copy_from_slice(&[-1, 0, 1]);
+
+        // unique for each process, so concurrent test runs do not share the file
+        let path = std::env::temp_dir().join(format!(
+            "megenginelite_test_safetensors_{}.safetensors",
+            std::process::id()
+        ));
+        save_safetensors(&path, &[("a", &a), ("b", &b)])?;
+
+        let file = SafeTensorsFile::open(&path)?;
+        let mut names = file.names();
+        names.sort_unstable();
+        assert_eq!(names, &["a", "b"]);
+        let other = file.tensor("a")?;
+        assert_eq!(other.shape(), &[2, 2]);
+        assert_eq!(other.as_slice::<f32>()?, &[1.0, 2.0, 3.0, 4.0]);
+        drop(file);
+        let other = other.slice(idx![1])?;
+        assert_eq!(other.as_slice::<f32>()?, &[3.0, 4.0]);
+
+        let tensors = SafeTensorsFile::open(&path)?.tensors()?;
+        assert_eq!(tensors.len(), 2);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
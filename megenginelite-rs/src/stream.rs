@@ -0,0 +1,290 @@
+//! The streaming inference of networks
+
+use super::pool::fit_pool;
+use super::{api, AsyncExec, LiteError, LiteResult, Network, OwnedPoolSlot, Tensor, TensorPool};
+use futures_core::Stream;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+/// The input tensors of an item of [`Network::stream`], by the input names
+pub type Inputs = HashMap<String, Tensor>;
+/// The output tensors of an item of [`Network::stream`], by the output names
+pub type Outputs = HashMap<String, Tensor>;
+
+/// The number of slots of each input, one is executed and the other is prepared
+const BUFFERS: usize = 2;
+
+impl Network {
+    /// Execute the network for each item of `inputs`, and yield the outputs in order
+    ///
+    /// The next item is copied into a host slot of a double-buffered [`TensorPool`]
+    /// while the current one is executed, at most one item is taken ahead from `inputs`.
+    /// The outputs are copied, so they are not overwritten by the later items. An item
+    /// with a name which is not an input of the network, or with other names than the
+    /// first item, yields an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use megenginelite_rs::*;
+    /// # use futures_core::Stream;
+    /// # use std::pin::Pin;
+    /// # #[tokio::main]
+    /// # async fn main() -> LiteResult<()> {
+    /// let mut network = Network::builder().build("model_path")?;
+    /// let (s, r) = async_channel::bounded(2);
+    /// tokio::spawn(async move {
+    ///     for _ in 0..8 {
+    ///         let mut data = Tensor::host().unwrap();
+    ///         data.set_layout(layout!(f32; 1, 3, 224, 224));
+    ///         // fill the data ...
+    ///         s.send(Inputs::from([("data".to_owned(), data)])).await.ok();
+    ///     }
+    /// });
+    /// let mut outputs = network.stream(r);
+    /// while let Some(rst) =
+    ///     std::future::poll_fn(|cx| Pin::new(&mut outputs).poll_next(cx)).await
+    /// {
+    ///     let output = &rst?["output"];
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream<S>(&mut self, inputs: S) -> NetworkStream<'_, S>
+    where
+        S: Stream<Item = Inputs>,
+    {
+        NetworkStream {
+            network: self,
+            inputs: Some(Box::pin(inputs)),
+            pools: HashMap::new(),
+            saved: HashMap::new(),
+            names: None,
+            prepared: None,
+            running: None,
+            bound: vec![],
+        }
+    }
+}
+
+/// The stream of the outputs, see also [`Network::stream`]
+///
+/// The inputs of the network are restored when the stream is dropped.
+pub struct NetworkStream<'a, S> {
+    network: &'a mut Network,
+    /// `None` if the inputs are exhausted
+    inputs: Option<Pin<Box<S>>>,
+    pools: HashMap<String, Option<Arc<TensorPool>>>,
+    /// The inputs of the network which borrow the slots, and their original views
+    saved: HashMap<String, (Tensor, Tensor)>,
+    /// The sorted input names of the first item, which every item must have
+    names: Option<Vec<String>>,
+    /// The next item copied into the slots
+    prepared: Option<LiteResult<Vec<(String, OwnedPoolSlot)>>>,
+    /// The execution of the current item
+    running: Option<AsyncExec>,
+    /// The slots of the current item, which are kept until the execution completes
+    bound: Vec<OwnedPoolSlot>,
+}
+
+impl<'a, S> NetworkStream<'a, S> {
+    fn prepare(&mut self, inputs: Inputs) -> LiteResult<Vec<(String, OwnedPoolSlot)>> {
+        // an omitted input would be executed with the slot of a former item
+        let mut names: Vec<_> = inputs.keys().cloned().collect();
+        names.sort();
+        let first = self.names.get_or_insert_with(|| names.clone());
+        if *first != names {
+            return Err(LiteError::MGELiteError(format!(
+                "the inputs {:?} differ from the inputs {:?} of the first item",
+                names, first
+            )));
+        }
+        let mut slots = vec![];
+        for (name, tensor) in inputs {
+            let pool = fit_pool(
+                self.pools.entry(name.clone()).or_default(),
+                &tensor,
+                BUFFERS,
+            )?;
+            // never miss because a slot is only held by the current and the next item
+            let mut slot = pool.try_get_owned().ok_or_else(|| {
+                LiteError::MGELiteError(format!("no free slot for the input {}", name))
+            })?;
+            slot.copy_from(&tensor)?;
+            slots.push((name, slot));
+        }
+        Ok(slots)
+    }
+
+    fn launch(&mut self, slots: Vec<(String, OwnedPoolSlot)>) -> LiteResult<()> {
+        // the outputs must not borrow the slots
+        let input_names = self.network.input_names();
+        if let Some((name, _)) = slots
+            .iter()
+            .find(|(name, _)| !input_names.contains(&name.as_str()))
+        {
+            return Err(LiteError::MGELiteError(format!("no input named {}", name)));
+        }
+        for (name, slot) in &slots {
+            let mut input = self.network.io_tensor(name).unwrap();
+            if input.is_host() {
+                if !self.saved.contains_key(name) {
+                    let handle = self.network.io_tensor(name).unwrap();
+                    self.saved.insert(name.clone(), (handle, input.view()?));
+                }
+                input.borrow_from(slot)?;
+            } else {
+                input.copy_from(slot)?;
+            }
+        }
+        self.bound = slots.into_iter().map(|(_, slot)| slot).collect();
+        self.running = Some(self.network.exec());
+        Ok(())
+    }
+
+    fn outputs(&self) -> LiteResult<Outputs> {
+        self.network
+            .output_names()
+            .into_iter()
+            .map(|name| {
+                let output = self.network.io_tensor(name).unwrap();
+                Ok((name.to_owned(), output.try_clone()?))
+            })
+            .collect()
+    }
+}
+
+impl<'a, S> Stream for NetworkStream<'a, S>
+where
+    S: Stream<Item = Inputs>,
+{
+    type Item = LiteResult<Outputs>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            // prepare the next item while the current one is executed
+            if this.prepared.is_none() {
+                match this.inputs.as_mut().map(|x| x.as_mut().poll_next(cx)) {
+                    Some(Poll::Ready(Some(item))) => this.prepared = Some(this.prepare(item)),
+                    Some(Poll::Ready(None)) => this.inputs = None,
+                    _ => {}
+                }
+            }
+
+            if let Some(running) = this.running.as_mut() {
+                let rst = match Pin::new(running).poll(cx) {
+                    Poll::Ready(rst) => rst,
+                    Poll::Pending => return Poll::Pending,
+                };
+                this.running = None;
+                let outputs = rst.and_then(|_| this.outputs());
+                this.bound.clear();
+                return Poll::Ready(Some(outputs));
+            }
+
+            match this.prepared.take() {
+                Some(Ok(slots)) => {
+                    if let Err(e) = this.launch(slots) {
+                        this.bound.clear();
+                        return Poll::Ready(Some(Err(e)));
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None if this.inputs.is_none() => return Poll::Ready(None),
+                None => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<'a, S> Drop for NetworkStream<'a, S> {
+    fn drop(&mut self) {
+        if self.running.is_some() {
+            unsafe { api().LITE_wait(self.network.inner) };
+        }
+        for (_, (mut input, saved)) in self.saved.drain() {
+            input.borrow_from(&saved).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use futures_core::Stream;
+    use std::pin::Pin;
+
+    #[tokio::test]
+    async fn test_stream() -> LiteResult<()> {
+        let mut network = Network::builder().build(crate::model_path())?;
+        let p = network.io_tensor("data").unwrap().as_ptr::<u8>();
+
+        let mut items = vec![];
+        for i in 0..3 {
+            let mut data = Tensor::host()?;
+            data.set_layout(layout!(f32; 1, 3, 224, 224));
+            data.as_slice_mut::<f32>()?.fill(i as f32);
+            items.push(Inputs::from([("data".to_owned(), data)]));
+        }
+        let name = network.output_names()[0].to_owned();
+        let mut expected = vec![];
+        for item in &items {
+            network
+                .io_tensor("data")
+                .unwrap()
+                .copy_from(&item["data"])?;
+            network.exec_wait()?;
+            expected.push(network.io_tensor(&name).unwrap().try_clone()?);
+        }
+        // the items give different outputs, so a stale input is caught
+        assert!(!expected[0].allclose(&expected[1], 1e-5, 1e-5)?);
+
+        let (s, r) = async_channel::unbounded();
+        for item in items {
+            s.try_send(item).unwrap();
+        }
+        drop(s);
+        let mut stream = network.stream(r);
+        let mut n = 0;
+        while let Some(outputs) =
+            std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+        {
+            let outputs = outputs?;
+            assert!(outputs[&name].allclose(&expected[n], 1e-5, 1e-5)?);
+            n += 1;
+        }
+        assert_eq!(n, 3);
+        drop(stream);
+        assert_eq!(network.io_tensor("data").unwrap().as_ptr::<u8>(), p);
+
+        let mut data = Tensor::host()?;
+        data.set_layout(layout!(f32; 1, 3, 224, 224));
+        let (s, r) = async_channel::unbounded();
+        s.try_send(Inputs::from([("data".to_owned(), data)]))
+            .unwrap();
+        s.try_send(Inputs::new()).unwrap();
+        drop(s);
+        let mut stream = network.stream(r);
+        let mut rsts = vec![];
+        while let Some(rst) = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            rsts.push(rst);
+        }
+        assert_eq!(rsts.len(), 2);
+        assert!(rsts[0].is_ok());
+        assert!(matches!(rsts[1], Err(LiteError::MGELiteError(_))));
+        drop(stream);
+
+        let mut data = Tensor::host()?;
+        data.set_layout(layout!(f32; 1, 1000));
+        let (s, r) = async_channel::unbounded();
+        s.try_send(Inputs::from([(name.clone(), data)])).unwrap();
+        drop(s);
+        let mut stream = network.stream(r);
+        let rst = std::future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await;
+        assert!(matches!(rst, Some(Err(LiteError::MGELiteError(_)))));
+        Ok(())
+    }
+}
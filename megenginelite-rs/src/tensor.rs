@@ -2,13 +2,36 @@
 
 use super::*;
 use crate::ffi::*;
+use std::any::Any;
+use std::sync::Arc;
+
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub enum SliceElem {
+    Index(isize),
+    Range {
+        start: Option<isize>,
+        end: Option<isize>,
+        step: isize,
+        inclusive: bool,
+    },
+    Ellipsis,
+    NewAxis,
+}
+
+impl SliceElem {
+    const FULL: SliceElem = SliceElem::Range {
+        start: None,
+        end: None,
+        step: 1,
+        inclusive: false,
+    };
+}
 
 #[doc(hidden)]
 #[derive(Debug)]
 pub struct SliceInfo<'a> {
-    pub start: &'a [usize],
-    pub end: &'a [Option<usize>],
-    pub step: &'a [usize],
+    pub elems: &'a [SliceElem],
 }
 
 /// The simple layout description
@@ -33,6 +56,8 @@ impl<'a> Default for Layout<'a> {
 pub struct Tensor {
     inner: LiteTensor,
     desc: LiteTensorDesc,
+    // keep the user memory used by the storage alive
+    owner: Option<Arc<dyn Any + Send + Sync>>,
 }
 
 unsafe impl Send for Tensor {}
@@ -61,8 +86,25 @@ impl Drop for Tensor {
 }
 
 impl Tensor {
-    pub(crate) fn new(inner: LiteTensor, desc: LiteTensorDesc) -> Tensor {
-        Tensor { inner, desc }
+    /// Wrap a raw tensor handle, the description is queried from the handle.
+    pub(crate) fn from_raw(inner: LiteTensor) -> Tensor {
+        let mut desc = LiteTensorDesc {
+            is_pinned_host: 0,
+            layout: Self::default_layout(),
+            device_type: DeviceType::CPU,
+            device_id: 0,
+        };
+        unsafe {
+            api().LITE_is_pinned_host(inner, &mut desc.is_pinned_host);
+            api().LITE_get_tensor_device_type(inner, &mut desc.device_type);
+            api().LITE_get_tensor_layout(inner, &mut desc.layout);
+            api().LITE_get_tensor_device_id(inner, &mut desc.device_id);
+        }
+        Tensor {
+            inner,
+            desc,
+            owner: None,
+        }
     }
 
     /// The storage memory of tensor is host memory.
@@ -78,7 +120,11 @@ impl Tensor {
             };
             api().LITE_make_tensor(desc, &mut inner).into_rst()?;
         };
-        Ok(Tensor { inner, desc })
+        Ok(Tensor {
+            inner,
+            desc,
+            owner: None,
+        })
     }
 
     /// The storage memory of the tensor is pinned memory, this is used
@@ -97,7 +143,11 @@ impl Tensor {
             };
             api().LITE_make_tensor(desc, &mut inner).into_rst()?;
         };
-        Ok(Tensor { inner, desc })
+        Ok(Tensor {
+            inner,
+            desc,
+            owner: None,
+        })
     }
 
     /// The storage memory of tensor is device memory.
@@ -115,7 +165,11 @@ impl Tensor {
             };
             api().LITE_make_tensor(desc, &mut inner).into_rst()?;
         };
-        Ok(Tensor { inner, desc })
+        Ok(Tensor {
+            inner,
+            desc,
+            owner: None,
+        })
     }
 
     pub fn shape(&self) -> &[usize] {
@@ -168,11 +222,58 @@ impl Tensor {
     /// Reshape a tensor with the memroy not change, the total number of
     /// element in the reshaped tensor must equal to the origin tensor, the input
     /// shape must only contain one or zero -1 to flag it can be deduced automatically.
-    pub fn reshape(&mut self, shape: &[i32]) {
+    ///
+    /// Return an error if the shape is invalid, or the memory is not continue.
+    pub fn reshape(&mut self, shape: &[i32]) -> LiteResult<()> {
+        if shape.len() > LAYOUT_MAX_DIM as usize {
+            return Err(LiteError::InvalidLayout(format!(
+                "reshape to {} dims, but the maximum dim is {}",
+                shape.len(),
+                LAYOUT_MAX_DIM
+            )));
+        }
+        if shape.iter().filter(|&&x| x == -1).count() > 1 {
+            return Err(LiteError::InvalidLayout(format!(
+                "reshape to {:?}, only one dim can be -1",
+                shape
+            )));
+        }
+        if let Some(i) = shape.iter().position(|&x| x == 0 || x < -1) {
+            return Err(LiteError::InvalidLayout(format!(
+                "reshape to {:?}, dim {} is invalid",
+                shape, i
+            )));
+        }
+        let total: usize = self.shape().iter().product();
+        let known: usize = shape
+            .iter()
+            .filter(|&&x| x > 0)
+            .map(|&x| x as usize)
+            .product();
+        let deduced = if shape.contains(&-1) {
+            total / known
+        } else {
+            1
+        };
+        if deduced * known != total {
+            return Err(LiteError::InvalidLayout(format!(
+                "cannot reshape {:?} to {:?}",
+                self.shape(),
+                shape
+            )));
+        }
+        if !self.is_continue() {
+            return Err(LiteError::InvalidLayout(
+                "the memory of tensor is not continue, see also `Tensor::to_contiguous`".into(),
+            ));
+        }
         unsafe {
-            api().LITE_tensor_reshape(self.inner, shape.as_ptr(), shape.len() as i32);
+            api()
+                .LITE_tensor_reshape(self.inner, shape.as_ptr(), shape.len() as i32)
+                .into_rst()?;
             api().LITE_get_tensor_layout(self.inner, &mut self.desc.layout);
         };
+        Ok(())
     }
 
     /// Fill zero to the tensor
@@ -190,37 +291,368 @@ impl Tensor {
     ///     data_type: DataType::U8,
     ///     shapes: &[1, 5],
     /// });
-    /// t.slice(idx![0, 0..2;2]);
+    /// t.slice(idx![0, 0..2;2]).unwrap();
     /// ```
-    pub fn slice(&self, info: SliceInfo) -> Tensor {
+    ///
+    /// The missing trailing dims are taken as a whole, negative indices are counted
+    /// from the end. Return an error if the param of any dim is out of range.
+    ///
+    /// `NewAxis` is implemented by reshape, so the sliced memory must be continue.
+    ///
+    /// The memory borrowed by a [`TensorView`] cannot be sliced, because the slices
+    /// would outlive the borrow.
+    pub fn slice(&self, info: SliceInfo) -> LiteResult<Tensor> {
+        self.check_shareable()?;
+        self.slice_unchecked(info)
+    }
+
+    fn slice_unchecked(&self, info: SliceInfo) -> LiteResult<Tensor> {
+        let shape = self.shape();
+        let ndim = shape.len();
+        let consumed = info
+            .elems
+            .iter()
+            .filter(|x| matches!(x, SliceElem::Index(_) | SliceElem::Range { .. }))
+            .count();
+        if consumed > ndim {
+            return Err(LiteError::InvalidSlice {
+                axis: ndim,
+                reason: format!("too many indices for tensor of {} dims: {}", ndim, consumed),
+            });
+        }
+        let ellipsis = info
+            .elems
+            .iter()
+            .filter(|x| matches!(x, SliceElem::Ellipsis))
+            .count();
+        if ellipsis > 1 {
+            return Err(LiteError::InvalidSlice {
+                axis: 0,
+                reason: "an index can only have a single ellipsis".into(),
+            });
+        }
+
+        let mut start = vec![0; ndim];
+        let mut end = shape.to_vec();
+        let mut step = vec![1; ndim];
+        let mut new_shape = Vec::with_capacity(ndim);
+        let mut new_axis = None;
+        let mut axis = 0;
+        for elem in info.elems {
+            let n = match *elem {
+                SliceElem::Index(_) | SliceElem::Range { .. } => shape[axis] as isize,
+                _ => 0,
+            };
+            let resolve = |x: isize| if x < 0 { x + n } else { x };
+            match *elem {
+                SliceElem::Index(i) => {
+                    let r = resolve(i);
+                    if r < 0 || r >= n {
+                        return Err(LiteError::InvalidSlice {
+                            axis,
+                            reason: format!("index {} is out of bounds {}", i, n),
+                        });
+                    }
+                    start[axis] = r as usize;
+                    end[axis] = r as usize + 1;
+                    new_shape.push(1);
+                    axis += 1;
+                }
+                SliceElem::Range {
+                    start: s,
+                    end: e,
+                    step: t,
+                    inclusive,
+                } => {
+                    let rs = s.map_or(0, resolve);
+                    let re = e.map_or(n, |x| resolve(x) + inclusive as isize);
+                    let reason = if t <= 0 {
+                        Some(format!("step {} must be greater than 0", t))
+                    } else if rs < 0 {
+                        Some(format!("start {:?} is out of bounds {}", s, n))
+                    } else if re > n {
+                        Some(format!("end {:?} is out of bounds {}", e, n))
+                    } else if rs >= re {
+                        Some(format!("start {} must be less than end {}", rs, re))
+                    } else {
+                        None
+                    };
+                    if let Some(reason) = reason {
+                        return Err(LiteError::InvalidSlice { axis, reason });
+                    }
+                    start[axis] = rs as usize;
+                    end[axis] = re as usize;
+                    step[axis] = t as usize;
+                    new_shape.push((re - rs + t - 1) / t);
+                    axis += 1;
+                }
+                SliceElem::Ellipsis => {
+                    for _ in 0..ndim - consumed {
+                        new_shape.push(shape[axis] as isize);
+                        axis += 1;
+                    }
+                }
+                SliceElem::NewAxis => {
+                    new_axis.get_or_insert(axis);
+                    new_shape.push(1);
+                }
+            }
+        }
+        new_shape.extend(shape[axis..].iter().map(|&x| x as isize));
+
         let mut desc = self.desc;
         let mut inner = std::ptr::null_mut();
-        let end: Vec<_> = info
-            .end
-            .iter()
-            .enumerate()
-            .map(|(i, x)| x.unwrap_or(self.desc.layout.shapes[i]))
-            .collect();
         unsafe {
-            api().LITE_tensor_slice(
-                self.inner,
-                info.start.as_ptr(),
-                end.as_ptr(),
-                info.step.as_ptr(),
-                info.start.len(),
-                &mut inner,
-            );
+            api()
+                .LITE_tensor_slice(
+                    self.inner,
+                    start.as_ptr(),
+                    end.as_ptr(),
+                    step.as_ptr(),
+                    start.len(),
+                    &mut inner,
+                )
+                .into_rst()?;
             api().LITE_get_tensor_layout(inner, &mut desc.layout);
         };
-        Tensor { inner, desc }
+        let mut tensor = Tensor {
+            inner,
+            desc,
+            owner: self.owner.clone(),
+        };
+        if let Some(axis) = new_axis {
+            if !tensor.is_continue() {
+                return Err(LiteError::InvalidSlice {
+                    axis,
+                    reason: "NewAxis requires the sliced memory to be continue".into(),
+                });
+            }
+            let new_shape: Vec<_> = new_shape.iter().map(|&x| x as i32).collect();
+            tensor.reshape(&new_shape)?;
+        }
+        Ok(tensor)
     }
 
-    /// Copy tensor form other tensor
-    pub fn copy_from(&mut self, other: &Tensor) {
+    /// Copy tensor form other tensor, the memory of them can be on different devices.
+    ///
+    /// The data type and shape of the tensor must be the same as `other`, except that a
+    /// tensor without layout (e.g. created by [`Tensor::host`]) takes the layout of
+    /// `other`.
+    pub fn copy_from(&mut self, other: &Tensor) -> LiteResult<()> {
+        if self.desc.layout.ndim != 0 {
+            if self.dtype() != other.dtype() {
+                return Err(LiteError::InvalidLayout(format!(
+                    "cannot copy a tensor of data type {} into data type {}",
+                    DataType::name(other.dtype()),
+                    DataType::name(self.dtype())
+                )));
+            }
+            if self.shape() != other.shape() {
+                return Err(LiteError::InvalidLayout(format!(
+                    "cannot copy a tensor of shape {:?} into shape {:?}",
+                    other.shape(),
+                    self.shape()
+                )));
+            }
+        }
+        let packed;
+        let src = if other.is_host() && !other.is_continue() {
+            packed = other.to_contiguous()?;
+            &packed
+        } else {
+            other
+        };
         unsafe {
-            api().LITE_tensor_copy(self.inner, other.inner);
+            api().LITE_tensor_copy(self.inner, src.inner).into_rst()?;
             api().LITE_get_tensor_layout(self.inner, &mut self.desc.layout);
         };
+        Ok(())
+    }
+
+    /// A deep copy of the tensor on the same device, the copy always has continue memory
+    pub fn try_clone(&self) -> LiteResult<Tensor> {
+        self.to_contiguous()
+    }
+
+    /// A copy of the tensor in host memory
+    pub fn to_host(&self) -> LiteResult<Tensor> {
+        self.copy_to(Tensor::host()?)
+    }
+
+    /// A copy of the tensor in the memory of device `ty` with id `dev_id`
+    ///
+    /// see also [`crate::DeviceType`], which is the alias of `LiteDeviceType`
+    pub fn to_device(&self, ty: LiteDeviceType, dev_id: i32) -> LiteResult<Tensor> {
+        self.copy_to(Tensor::device(ty, dev_id)?)
+    }
+
+    /// A copy of the tensor in the pinned memory of device `ty` with id `dev_id`,
+    /// see also [`Tensor::pinned_host`]
+    pub fn to_pinned(&self, ty: LiteDeviceType, dev_id: i32) -> LiteResult<Tensor> {
+        self.copy_to(Tensor::pinned_host(ty, dev_id)?)
+    }
+
+    fn copy_to(&self, mut dst: Tensor) -> LiteResult<Tensor> {
+        dst.copy_from(self)?;
+        Ok(dst)
+    }
+
+    /// Concat `tensors` along `axis`, the result is a new tensor on the device `ty`
+    /// with id `dev_id`.
+    ///
+    /// All tensors must have the same data type, and the same shape except in the
+    /// dimension `axis`.
+    ///
+    /// see also [`crate::DeviceType`], which is the alias of `LiteDeviceType`
+    pub fn concat(
+        tensors: &[&Tensor],
+        axis: usize,
+        ty: LiteDeviceType,
+        dev_id: i32,
+    ) -> LiteResult<Tensor> {
+        let first = tensors.first().ok_or_else(|| {
+            LiteError::InvalidLayout("concat requires at least one tensor".into())
+        })?;
+        if axis >= first.shape().len() {
+            return Err(LiteError::InvalidLayout(format!(
+                "concat axis {} is out of range for tensor of {} dims",
+                axis,
+                first.shape().len()
+            )));
+        }
+        for (i, t) in tensors.iter().enumerate().skip(1) {
+            if t.dtype() != first.dtype() {
+                return Err(LiteError::InvalidLayout(format!(
+                    "concat tensor {} has data type {}, expected {}",
+                    i,
+                    DataType::name(t.dtype()),
+                    DataType::name(first.dtype())
+                )));
+            }
+            let same = t.shape().len() == first.shape().len()
+                && t.shape()
+                    .iter()
+                    .zip(first.shape())
+                    .enumerate()
+                    .all(|(d, (a, b))| d == axis || a == b);
+            if !same {
+                return Err(LiteError::InvalidLayout(format!(
+                    "concat tensor {} has shape {:?}, which is not compatible with {:?} at axis {}",
+                    i,
+                    t.shape(),
+                    first.shape(),
+                    axis
+                )));
+            }
+        }
+
+        let mut raw: Vec<_> = tensors.iter().map(|t| t.inner).collect();
+        let mut inner = std::ptr::null_mut();
+        unsafe {
+            api()
+                .LITE_tensor_concat(
+                    raw.as_mut_ptr(),
+                    raw.len() as i32,
+                    axis as i32,
+                    ty,
+                    dev_id,
+                    &mut inner,
+                )
+                .into_rst()?
+        };
+        Ok(Tensor::from_raw(inner))
+    }
+
+    /// Stack `tensors` along a new dimension `axis`, the result is a new tensor on
+    /// the device `ty` with id `dev_id`.
+    ///
+    /// All tensors must have the same data type and shape.
+    ///
+    /// see also [`crate::DeviceType`], which is the alias of `LiteDeviceType`
+    pub fn stack(
+        tensors: &[&Tensor],
+        axis: usize,
+        ty: LiteDeviceType,
+        dev_id: i32,
+    ) -> LiteResult<Tensor> {
+        let first = tensors
+            .first()
+            .ok_or_else(|| LiteError::InvalidLayout("stack requires at least one tensor".into()))?;
+        let ndim = first.shape().len();
+        if axis > ndim || ndim >= LAYOUT_MAX_DIM as usize {
+            return Err(LiteError::InvalidLayout(format!(
+                "stack axis {} is out of range for tensor of {} dims",
+                axis, ndim
+            )));
+        }
+        for (i, t) in tensors.iter().enumerate().skip(1) {
+            if t.dtype() != first.dtype() || t.shape() != first.shape() {
+                return Err(LiteError::InvalidLayout(format!(
+                    "stack tensor {} has layout {:?}/{}, expected {:?}/{}",
+                    i,
+                    t.shape(),
+                    DataType::name(t.dtype()),
+                    first.shape(),
+                    DataType::name(first.dtype())
+                )));
+            }
+        }
+
+        let mut shape: Vec<i32> = first.shape().iter().map(|&x| x as i32).collect();
+        shape.insert(axis, 1);
+        let mut views = Vec::with_capacity(tensors.len());
+        for t in tensors {
+            let mut view = if t.is_continue() {
+                t.view()?
+            } else {
+                t.to_contiguous()?
+            };
+            view.reshape(&shape)?;
+            views.push(view);
+        }
+        let views: Vec<_> = views.iter().collect();
+        Self::concat(&views, axis, ty, dev_id)
+    }
+
+    /// Split the tensor along `axis` into views, the i-th view has `sizes[i]`
+    /// elements in the dimension `axis`, see also [`Tensor::slice`].
+    ///
+    /// The sum of `sizes` must be equal to the dimension `axis`.
+    pub fn split(&self, axis: usize, sizes: &[usize]) -> LiteResult<Vec<Tensor>> {
+        let shape = self.shape();
+        if axis >= shape.len() {
+            return Err(LiteError::InvalidLayout(format!(
+                "split axis {} is out of range for tensor of {} dims",
+                axis,
+                shape.len()
+            )));
+        }
+        if sizes.contains(&0) || sizes.iter().sum::<usize>() != shape[axis] {
+            return Err(LiteError::InvalidLayout(format!(
+                "split sizes {:?} do not sum to {} at axis {}",
+                sizes, shape[axis], axis
+            )));
+        }
+
+        let mut elems = vec![SliceElem::FULL; axis + 1];
+        let mut start = 0;
+        let mut views = Vec::with_capacity(sizes.len());
+        for size in sizes {
+            elems[axis] = SliceElem::Range {
+                start: Some(start as isize),
+                end: Some((start + size) as isize),
+                step: 1,
+                inclusive: false,
+            };
+            views.push(self.slice(SliceInfo { elems: &elems })?);
+            start += size;
+        }
+        Ok(views)
+    }
+
+    /// A view of the whole tensor which shares the memory
+    pub(crate) fn view(&self) -> LiteResult<Tensor> {
+        self.slice_unchecked(SliceInfo { elems: &[] })
     }
 
     /// Get the memory pointer of a Tensor object.
@@ -241,33 +673,144 @@ impl Tensor {
         p as *mut T
     }
 
-    /// As a slice
-    ///
-    /// # Panic
-    /// if the tensor is not a host tensor
-    pub fn as_slice<T>(&self) -> &[T] {
+    /// Get the strides of the tensor in elements, the stride of a dimension with
+    /// size less than 2 is 0.
+    pub fn strides(&self) -> Vec<usize> {
+        let shape = self.shape();
+        let width = DataType::width(self.dtype());
+        let base = self.as_ptr::<u8>() as usize;
+        (0..shape.len())
+            .map(|i| {
+                if shape[i] < 2 {
+                    return 0;
+                }
+                let mut index = vec![0; shape.len()];
+                index[i] = 1;
+                let mut p = std::ptr::null_mut();
+                unsafe {
+                    api().LITE_get_tensor_memory_with_index(
+                        self.inner,
+                        index.as_ptr(),
+                        index.len(),
+                        &mut p,
+                    )
+                };
+                (p as usize - base) / width
+            })
+            .collect()
+    }
+
+    /// Copy the tensor into a new tensor with continue memory on the same device,
+    /// this is useful to pack a view created by [`Tensor::slice`].
+    pub fn to_contiguous(&self) -> LiteResult<Tensor> {
+        let mut dst = self.empty_like()?;
+        if self.shape().is_empty() {
+            return Ok(dst);
+        }
         if !self.is_host() {
-            panic!("as_slice only support for host tensor")
+            unsafe { api().LITE_tensor_copy(dst.inner, self.inner).into_rst()? };
+            return Ok(dst);
         }
-        unsafe {
-            std::slice::from_raw_parts(self.as_ptr(), self.nbytes() / std::mem::size_of::<T>())
+
+        let width = DataType::width(self.dtype());
+        let src = self.as_ptr::<u8>();
+        let p = dst.as_ptr_mut::<u8>();
+        for (i, offset) in Offsets::new(self.shape(), &self.strides()).enumerate() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(src.add(offset * width), p.add(i * width), width)
+            };
+        }
+        Ok(dst)
+    }
+
+    /// A new tensor with the same layout on the same device
+    fn empty_like(&self) -> LiteResult<Tensor> {
+        let desc = self.desc;
+        let mut inner = std::ptr::null_mut();
+        unsafe { api().LITE_make_tensor(desc, &mut inner).into_rst()? };
+        Ok(Tensor {
+            inner,
+            desc,
+            owner: None,
+        })
+    }
+
+    pub(crate) fn check_host(&self) -> LiteResult<()> {
+        if self.is_host() {
+            Ok(())
+        } else {
+            Err(LiteError::InvalidDevice(
+                "the operation only supports host tensor".into(),
+            ))
+        }
+    }
+
+    pub(crate) fn check_element<T>(&self) -> LiteResult<()> {
+        if std::mem::size_of::<T>() == DataType::width(self.dtype()) {
+            Ok(())
+        } else {
+            Err(LiteError::InvalidLayout(format!(
+                "element size {} does not match data type {}",
+                std::mem::size_of::<T>(),
+                self.dtype()
+            )))
+        }
+    }
+
+    fn check_shareable(&self) -> LiteResult<()> {
+        match &self.owner {
+            Some(owner) if (**owner).is::<Borrowed>() => Err(LiteError::BorrowedMemory),
+            _ => Ok(()),
         }
     }
 
+    fn check_slice(&self) -> LiteResult<()> {
+        self.check_host()?;
+        if self.is_continue() {
+            Ok(())
+        } else {
+            Err(LiteError::InvalidLayout(
+                "the memory of tensor is not continue, see also `Tensor::to_contiguous`".into(),
+            ))
+        }
+    }
+
+    /// As a slice
+    ///
+    /// Return an error if the tensor is not a host tensor, or the memory is not continue.
+    pub fn as_slice<T>(&self) -> LiteResult<&[T]> {
+        self.check_slice()?;
+        Ok(unsafe {
+            std::slice::from_raw_parts(self.as_ptr(), self.nbytes() / std::mem::size_of::<T>())
+        })
+    }
+
     /// As a mutable slice
     ///
-    /// # Panic
-    /// if the tensor is not a host tensor
-    pub fn as_slice_mut<T>(&mut self) -> &mut [T] {
-        if !self.is_host() {
-            panic!("as_slice_mut only support for host tensor")
-        }
-        unsafe {
+    /// Return an error if the tensor is not a host tensor, or the memory is not continue.
+    pub fn as_slice_mut<T>(&mut self) -> LiteResult<&mut [T]> {
+        self.check_slice()?;
+        Ok(unsafe {
             std::slice::from_raw_parts_mut(
                 self.as_ptr_mut(),
                 self.nbytes() / std::mem::size_of::<T>(),
             )
-        }
+        })
+    }
+
+    /// An iterator over the elements in logical order, it walks the views created by
+    /// [`Tensor::slice`] correctly.
+    ///
+    /// Return an error if the tensor is not a host tensor, or the size of `T` does not
+    /// match the data type.
+    pub fn iter<T>(&self) -> LiteResult<StridedIter<T>> {
+        self.check_host()?;
+        self.check_element::<T>()?;
+        Ok(StridedIter {
+            ptr: self.as_ptr(),
+            offsets: Offsets::new(self.shape(), &self.strides()),
+            phantom: std::marker::PhantomData,
+        })
     }
 
     /// As a [`ndarray::ArrayView`]
@@ -276,7 +819,10 @@ impl Tensor {
     /// if the tensor is not a host tensor
     #[cfg(feature = "ndarray-basis")]
     pub fn as_ndarray<T>(&self) -> ndarray::ArrayView<T, ndarray::IxDyn> {
-        ndarray::ArrayView::from_shape(self.shape(), self.as_slice()).unwrap()
+        use ndarray::ShapeBuilder;
+        assert!(self.is_host(), "as_ndarray only support for host tensor");
+        let shape = ndarray::IxDyn(self.shape()).strides(ndarray::IxDyn(&self.strides()));
+        unsafe { ndarray::ArrayView::from_shape_ptr(shape, self.as_ptr()) }
     }
 
     /// As a [`ndarray::ArrayViewMut`]
@@ -285,17 +831,68 @@ impl Tensor {
     /// if the tensor is not a host tensor
     #[cfg(feature = "ndarray-basis")]
     pub fn as_ndarray_mut<T>(&mut self) -> ndarray::ArrayViewMut<T, ndarray::IxDyn> {
-        let shape = self.shape();
-        let p: *const T = self.as_ptr();
-        unsafe { ndarray::ArrayViewMut::from_shape_ptr(shape, p as *mut _) }
+        use ndarray::ShapeBuilder;
+        assert!(
+            self.is_host(),
+            "as_ndarray_mut only support for host tensor"
+        );
+        let shape = ndarray::IxDyn(self.shape()).strides(ndarray::IxDyn(&self.strides()));
+        unsafe { ndarray::ArrayViewMut::from_shape_ptr(shape, self.as_ptr_mut()) }
     }
 
     /// Borrow the memory from the `other`, the self memory will be freed
-    pub fn borrow_from<'a, 'b: 'a>(&'b mut self, other: &'a Tensor) {
+    ///
+    /// The memory is refcounted, both the memory allocated by megenginelite and the
+    /// user memory held by `other` (e.g. [`Tensor::with_buffer`]), so `other` can be
+    /// dropped while `self` still uses the memory.
+    ///
+    /// Return an error if `other` is the memory borrowed by a [`TensorView`].
+    pub fn borrow_from(&mut self, other: &Tensor) -> LiteResult<()> {
+        other.check_shareable()?;
         unsafe {
-            api().LITE_tensor_share_memory_with(self.inner, other.inner);
+            api()
+                .LITE_tensor_share_memory_with(self.inner, other.inner)
+                .into_rst()?;
             api().LITE_get_tensor_layout(self.inner, &mut self.desc.layout);
         }
+        self.owner = other.owner.clone();
+        Ok(())
+    }
+
+    /// Reset the memory of the tensor to `p`, `owner` which holds the memory is kept
+    /// alive as long as the tensor and its views.
+    ///
+    /// # Safety
+    /// `p` must be valid for `nbytes` bytes as long as `owner` is alive.
+    pub(crate) unsafe fn reset_memory(
+        &mut self,
+        p: *mut std::ffi::c_void,
+        nbytes: usize,
+        owner: Arc<dyn Any + Send + Sync>,
+    ) -> LiteResult<()> {
+        api()
+            .LITE_reset_tensor_memory(self.inner, p, nbytes)
+            .into_rst()?;
+        self.owner = Some(owner);
+        Ok(())
+    }
+
+    /// Keep `owner` alive as long as the tensor and its views, besides the current owner
+    pub(crate) fn hold(&mut self, owner: Arc<dyn Any + Send + Sync>) {
+        self.owner = Some(Arc::new((self.owner.take(), owner)));
+    }
+
+    /// A host copy of the tensor with continue memory, or `None` if the tensor is
+    /// already a continue host tensor.
+    #[cfg(any(feature = "npy", feature = "safetensors-basis"))]
+    pub(crate) fn packed_host(&self) -> LiteResult<Option<Tensor>> {
+        if !self.is_host() {
+            Ok(Some(self.to_host()?))
+        } else if !self.is_continue() {
+            Ok(Some(self.to_contiguous()?))
+        } else {
+            Ok(None)
+        }
     }
 
     /// Use the user allocated data to reset the memory of the tensor
@@ -304,6 +901,9 @@ impl Tensor {
     ///
     /// # Safety
     /// the memory will not be managed by the lite, later, the user should delete it.
+    ///
+    /// see also [`Tensor::with_buffer`], [`Tensor::with_aligned_buffer`] and
+    /// [`TensorView`] for the safe alternatives.
     pub unsafe fn borrow_from_raw_parts<T>(&mut self, p: *mut T, length: usize) {
         let nbytes = length * std::mem::size_of::<T>();
         assert_eq!(nbytes, self.nbytes());
@@ -319,6 +919,81 @@ impl Tensor {
     }
 }
 
+/// An iterator over the elements of a tensor, see also [`Tensor::iter`]
+pub struct StridedIter<'a, T> {
+    ptr: *const T,
+    offsets: Offsets,
+    phantom: std::marker::PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for StridedIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.offsets
+            .next()
+            .map(|offset| unsafe { &*self.ptr.add(offset) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.offsets.size_hint()
+    }
+}
+
+impl<'a, T> ExactSizeIterator for StridedIter<'a, T> {}
+
+/// The element offsets of a strided layout in logical order
+pub(crate) struct Offsets {
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+    index: Vec<usize>,
+    offset: usize,
+    remaining: usize,
+}
+
+impl Offsets {
+    pub(crate) fn new(shape: &[usize], strides: &[usize]) -> Offsets {
+        Offsets {
+            shape: shape.to_vec(),
+            strides: strides.to_vec(),
+            index: vec![0; shape.len()],
+            offset: 0,
+            // a tensor without layout has no element
+            remaining: if shape.is_empty() {
+                0
+            } else {
+                shape.iter().product()
+            },
+        }
+    }
+}
+
+impl Iterator for Offsets {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let offset = self.offset;
+        for i in (0..self.shape.len()).rev() {
+            self.index[i] += 1;
+            self.offset += self.strides[i];
+            if self.index[i] < self.shape[i] {
+                break;
+            }
+            self.offset -= self.strides[i] * self.index[i];
+            self.index[i] = 0;
+        }
+        Some(offset)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::*;
@@ -346,22 +1021,169 @@ mod test {
     #[test]
     fn test_slice() {
         let tensor = get_tensor(100, 200);
-        let sub = tensor.slice(idx![0, 0..100]);
+        let sub = tensor.slice(idx![0, 0..100]).unwrap();
         assert_eq!(sub.shape()[0], 1);
         assert_eq!(sub.shape()[1], 100);
         assert!(sub.is_continue());
 
-        let sub = tensor.slice(idx![10..50, 50..100]);
+        let sub = tensor.slice(idx![10..50, 50..100]).unwrap();
         assert_eq!(sub.shape()[0], 40);
         assert_eq!(sub.shape()[1], 50);
         assert!(!sub.is_continue());
+
+        let sub = tensor.slice(idx![10..50]).unwrap();
+        assert_eq!(sub.shape(), &[40, 200]);
+
+        assert!(matches!(
+            tensor.slice(idx![0, 0..201]),
+            Err(LiteError::InvalidSlice { axis: 1, .. })
+        ));
+        assert!(matches!(
+            tensor.slice(idx![0..0]),
+            Err(LiteError::InvalidSlice { axis: 0, .. })
+        ));
+        let step = 0;
+        assert!(matches!(
+            tensor.slice(idx![0, 0..2;step]),
+            Err(LiteError::InvalidSlice { axis: 1, .. })
+        ));
+        assert!(tensor.slice(idx![0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_slice_numpy_like() {
+        let tensor = get_tensor(100, 200);
+        let sub = tensor.slice(idx![-1, ..-50]).unwrap();
+        assert_eq!(sub.shape(), &[1, 150]);
+        let sub = tensor.slice(idx![-10..;3, -3..=-1]).unwrap();
+        assert_eq!(sub.shape(), &[4, 3]);
+
+        let sub = tensor.slice(idx![..., 0..10]).unwrap();
+        assert_eq!(sub.shape(), &[100, 10]);
+        let sub = tensor.slice(idx![0..10, ...]).unwrap();
+        assert_eq!(sub.shape(), &[10, 200]);
+
+        let sub = tensor.slice(idx![NewAxis, ...]).unwrap();
+        assert_eq!(sub.shape(), &[1, 100, 200]);
+        let sub = tensor.slice(idx![0, NewAxis, ..]).unwrap();
+        assert_eq!(sub.shape(), &[1, 1, 200]);
+        assert!(matches!(
+            tensor.slice(idx![0..10, NewAxis, 0..10]),
+            Err(LiteError::InvalidSlice { axis: 1, .. })
+        ));
+
+        assert!(matches!(
+            tensor.slice(idx![-101]),
+            Err(LiteError::InvalidSlice { axis: 0, .. })
+        ));
+        let step = -1;
+        assert!(matches!(
+            tensor.slice(idx![.., ..;step]),
+            Err(LiteError::InvalidSlice { axis: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_reshape() {
+        let mut tensor = get_tensor(100, 200);
+        tensor.reshape(&[10, -1, 4]).unwrap();
+        assert_eq!(tensor.shape(), &[10, 500, 4]);
+        assert!(tensor.reshape(&[3, -1]).is_err());
+        assert!(tensor.reshape(&[-1, -1]).is_err());
+        assert!(tensor.reshape(&[100, 201]).is_err());
+
+        let mut sub = tensor.slice(idx![0..5, 0..10]).unwrap();
+        assert!(sub.reshape(&[-1]).is_err());
+    }
+
+    #[test]
+    fn test_concat() {
+        let a = get_tensor(10, 20);
+        let b = get_tensor(5, 20);
+        let c = Tensor::concat(&[&a, &b], 0, DeviceType::CPU, 0).unwrap();
+        assert_eq!(c.shape(), &[15, 20]);
+        assert_eq!(c.dtype(), DataType::U8);
+
+        assert!(Tensor::concat(&[&a, &b], 1, DeviceType::CPU, 0).is_err());
+        assert!(Tensor::concat(&[], 0, DeviceType::CPU, 0).is_err());
+    }
+
+    #[test]
+    fn test_stack() {
+        let a = get_tensor(10, 20);
+        let b = get_tensor(10, 20);
+        let c = Tensor::stack(&[&a, &b], 0, DeviceType::CPU, 0).unwrap();
+        assert_eq!(c.shape(), &[2, 10, 20]);
+        let c = Tensor::stack(&[&a, &b], 2, DeviceType::CPU, 0).unwrap();
+        assert_eq!(c.shape(), &[10, 20, 2]);
+
+        let b = get_tensor(5, 20);
+        assert!(Tensor::stack(&[&a, &b], 0, DeviceType::CPU, 0).is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        let tensor = get_tensor(10, 20);
+        let views = tensor.split(1, &[5, 15]).unwrap();
+        assert_eq!(views.len(), 2);
+        assert_eq!(views[0].shape(), &[10, 5]);
+        assert_eq!(views[1].shape(), &[10, 15]);
+        assert!(tensor.split(1, &[5, 5]).is_err());
+        assert!(tensor.split(2, &[10]).is_err());
+    }
+
+    #[test]
+    fn test_to_contiguous() {
+        let mut tensor = get_tensor(10, 20);
+        for (i, x) in tensor.as_slice_mut::<u8>().unwrap().iter_mut().enumerate() {
+            *x = i as u8;
+        }
+        let sub = tensor.slice(idx![2..4, 5..10;2]).unwrap();
+        assert!(!sub.is_continue());
+        assert_eq!(sub.strides(), &[20, 2]);
+        assert!(sub.as_slice::<u8>().is_err());
+
+        let packed = sub.to_contiguous().unwrap();
+        assert!(packed.is_continue());
+        assert_eq!(packed.shape(), &[2, 3]);
+        let expected: Vec<u8> = vec![45, 47, 49, 65, 67, 69];
+        assert_eq!(packed.as_slice::<u8>().unwrap(), &expected[..]);
+        let walked: Vec<u8> = sub.iter::<u8>().unwrap().copied().collect();
+        assert_eq!(walked, expected);
+        assert!(sub.iter::<f32>().is_err());
+
+        let empty = Tensor::host().unwrap();
+        assert!(empty.to_contiguous().unwrap().shape().is_empty());
+        assert_eq!(Offsets::new(&[], &[]).count(), 0);
+    }
+
+    #[test]
+    fn test_shape_macro() {
+        const N: usize = 4;
+        let n = 2;
+        assert_eq!(shape!(1, 3, 224, 224), [1usize, 3, 224, 224]);
+        assert_eq!(shape!(N, N * 2, n), [4usize, 8, 2]);
+        assert_eq!(shape!(N, -1), [4i32, -1]);
+        assert_eq!(shape!(), [0usize; 0]);
+
+        let layout = layout!(f32; 1, 3, N);
+        assert_eq!(layout.data_type, DataType::F32);
+        assert_eq!(layout.shapes, &[1, 3, 4]);
+        let layout = layout!(DataType::U8; n, n);
+        assert_eq!(layout.data_type, DataType::U8);
+        assert_eq!(layout.shapes, &[2, 2]);
+
+        let mut tensor = Tensor::host().unwrap();
+        tensor.set_layout(layout!(u8; 10, 20));
+        tensor.reshape(&shape!(5, -1)).unwrap();
+        assert_eq!(tensor.shape(), &shape!(5, 40));
     }
 
     #[test]
     fn test_fill_zero() {
         let mut tensor = get_tensor(10, 20);
         tensor.fill_zero();
-        for &i in tensor.as_slice::<u8>() {
+        for &i in tensor.as_slice::<u8>().unwrap() {
             assert_eq!(i, 0);
         }
     }
@@ -369,28 +1191,78 @@ mod test {
     #[test]
     fn test_copy_from() {
         let mut tensor = get_tensor(10, 20);
-        let slice = tensor.as_slice_mut::<u8>();
+        let slice = tensor.as_slice_mut::<u8>().unwrap();
         slice.iter_mut().enumerate().for_each(|(i, x)| {
             *x = i as u8;
         });
         let mut other = Tensor::host().unwrap();
-        other.copy_from(&tensor);
+        other.copy_from(&tensor).unwrap();
         let zip = tensor
             .as_slice::<u8>()
+            .unwrap()
             .iter()
-            .zip(other.as_slice::<u8>().iter());
+            .zip(other.as_slice::<u8>().unwrap().iter());
         for (a, b) in zip {
             assert_eq!(a, b);
         }
     }
 
+    #[test]
+    fn test_copy_from_mismatch() {
+        let tensor = get_tensor(10, 20);
+        let mut other = get_tensor(20, 10);
+        assert!(matches!(
+            other.copy_from(&tensor),
+            Err(LiteError::InvalidLayout(_))
+        ));
+        let mut other = Tensor::host().unwrap();
+        other.set_layout(layout!(f32; 10, 20));
+        assert!(matches!(
+            other.copy_from(&tensor),
+            Err(LiteError::InvalidLayout(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let mut tensor = get_tensor(10, 20);
+        tensor.fill_zero();
+        let other = tensor.try_clone().unwrap();
+        tensor.as_slice_mut::<u8>().unwrap()[0] = 1;
+        assert_eq!(other.shape(), &[10, 20]);
+        assert_eq!(other.as_slice::<u8>().unwrap()[0], 0);
+        assert_ne!(other.as_ptr::<u8>(), tensor.as_ptr::<u8>());
+
+        let sub = tensor.slice(idx![..;2, 3]).unwrap();
+        let host = sub.to_host().unwrap();
+        assert!(host.is_host() && host.is_continue());
+        assert_eq!(host.shape(), &[5, 1]);
+        assert_eq!(host.as_slice::<u8>().unwrap(), &[0; 5]);
+
+        let empty = Tensor::host().unwrap().try_clone().unwrap();
+        assert!(empty.shape().is_empty());
+    }
+
     #[test]
     fn test_borrow_from() {
         let other_tensor = get_tensor(10, 20);
         let mut tensor = get_tensor(10, 20);
 
-        tensor.borrow_from(&other_tensor);
+        tensor.borrow_from(&other_tensor).unwrap();
 
         assert_eq!(other_tensor.as_ptr::<u8>(), tensor.as_ptr::<u8>());
+
+        let data = vec![1u8; 200].into_boxed_slice();
+        let other_tensor = Tensor::with_buffer(layout!(u8; 10, 20), data).unwrap();
+        tensor.borrow_from(&other_tensor).unwrap();
+        drop(other_tensor);
+        assert_eq!(tensor.as_slice::<u8>().unwrap(), &[1; 200]);
+
+        let mut data = [0u8; 200];
+        let view = TensorView::new(layout!(u8; 10, 20), &mut data).unwrap();
+        assert!(matches!(
+            tensor.borrow_from(&view),
+            Err(LiteError::BorrowedMemory)
+        ));
     }
 }
@@ -11,6 +11,38 @@ pub enum LiteError {
     LoadingFault,
     /// The version is not match
     VersionNotMatch(String),
+    /// The layout of tensor is invalid for the operation
+    InvalidLayout(String),
+    /// The device of tensor is invalid for the operation
+    InvalidDevice(String),
+    /// The slice parameter of the `axis` is invalid
+    InvalidSlice { axis: usize, reason: String },
+    /// An I/O error
+    IOError(std::io::Error),
+    /// The file format is invalid or not supported
+    InvalidFormat(String),
+    /// The memory borrowed by a [`crate::TensorView`] cannot be shared beyond the borrow
+    BorrowedMemory,
+    /// The memory is exhausted
+    OutOfMemory(String),
+    /// The pool is closed
+    PoolClosed,
+    /// The [`crate::Pipeline`] is closed
+    PipelineClosed,
+    /// The batch of [`crate::Batcher`] which the request belongs to fails, the error is
+    /// shared by all requests of the batch
+    BatchFailed(std::sync::Arc<LiteError>),
+    /// The stage of a [`crate::Pipeline`] fails to process a frame
+    StageFailed {
+        stage: String,
+        error: Box<LiteError>,
+    },
+}
+
+impl From<std::io::Error> for LiteError {
+    fn from(e: std::io::Error) -> Self {
+        LiteError::IOError(e)
+    }
 }
 
 /// A type to describe device
@@ -24,6 +56,18 @@ impl DeviceType {
     pub const ATLAS: LiteDeviceType = LiteDeviceType_LITE_ATLAS;
     pub const DEFAULT: LiteDeviceType = LiteDeviceType_LITE_DEVICE_DEFAULT;
     pub const CAMBRICON: LiteDeviceType = LiteDeviceType_LITE_CAMBRICON;
+
+    pub fn name(ty: LiteDeviceType) -> &'static str {
+        match ty {
+            Self::CPU => "CPU",
+            Self::CUDA => "CUDA",
+            Self::NPU => "NPU",
+            Self::ATLAS => "ATLAS",
+            Self::DEFAULT => "DEFAULT",
+            Self::CAMBRICON => "CAMBRICON",
+            _ => "UNKNOWN",
+        }
+    }
 }
 
 /// A type to describe data
@@ -55,6 +99,21 @@ impl DataType {
             _ => unreachable!(),
         }
     }
+
+    pub fn name(ty: LiteDataType) -> &'static str {
+        match ty {
+            Self::F32 => "f32",
+            Self::F16 => "f16",
+            Self::I32 => "i32",
+            Self::I16 => "i16",
+            Self::I8 => "i8",
+            Self::U8 => "u8",
+            Self::U32 => "u32",
+            Self::U16 => "u16",
+            Self::I64 => "i64",
+            _ => "unknown",
+        }
+    }
 }
 
 /// A type to describe fastrun strategy
@@ -14,3 +14,95 @@ pub fn path_to_cstr(path: &Path) -> CString {
 
     CString::new(bytes).unwrap()
 }
+
+/// Convert the bits of a IEEE 754 half-precision float to `f32`
+pub fn f16_to_f32(h: u16) -> f32 {
+    let sign = ((h & 0x8000) as u32) << 16;
+    let exp = ((h >> 10) & 0x1f) as u32;
+    let man = (h & 0x3ff) as u32;
+    let bits = match (exp, man) {
+        (0, 0) => sign,
+        // subnormal, normalize it
+        (0, _) => {
+            let shift = man.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | ((man << shift) & 0x3ff) << 13
+        }
+        (0x1f, 0) => sign | 0x7f80_0000,
+        (0x1f, _) => sign | 0x7fc0_0000 | man << 13,
+        _ => sign | (exp + 112) << 23 | man << 13,
+    };
+    f32::from_bits(bits)
+}
+
+/// Convert a `f32` to the bits of a IEEE 754 half-precision float, rounding to the
+/// nearest even
+pub fn f32_to_f16(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let man = bits & 0x7f_ffff;
+    if exp == 0xff {
+        let nan = if man != 0 {
+            0x200 | (man >> 13) as u16
+        } else {
+            0
+        };
+        return sign | 0x7c00 | nan;
+    }
+    let exp = exp - 112;
+    if exp >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // the mantissa of half and the truncated bits
+    let (half, rem, shift) = if exp <= 0 {
+        // subnormal or zero
+        if exp < -10 {
+            return sign;
+        }
+        let man = man | 0x80_0000;
+        let shift = (14 - exp) as u32;
+        (man >> shift, man & ((1 << shift) - 1), shift)
+    } else {
+        ((exp as u32) << 10 | man >> 13, man & 0x1fff, 13)
+    };
+    let halfway = 1 << (shift - 1);
+    let round = rem > halfway || (rem == halfway && half & 1 == 1);
+    // the carry of rounding goes into the exponent, which is expected
+    sign | (half + round as u32) as u16
+}
+
+struct ThreadWaker(std::thread::Thread);
+
+impl std::task::Wake for ThreadWaker {
+    fn wake(self: std::sync::Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Run `fut` to completion on the current thread, return `None` if it is not ready
+/// before `deadline`.
+pub fn block_on<F: std::future::Future>(
+    fut: F,
+    deadline: Option<std::time::Instant>,
+) -> Option<F::Output> {
+    use std::task::{Context, Poll, Waker};
+
+    let mut fut = Box::pin(fut);
+    let waker = Waker::from(std::sync::Arc::new(ThreadWaker(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+        match deadline {
+            None => std::thread::park(),
+            Some(deadline) => {
+                let now = std::time::Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                std::thread::park_timeout(deadline - now);
+            }
+        }
+    }
+}